@@ -0,0 +1,58 @@
+// A minimal dashboard with a spinning cube next to a regular ratatui widget.
+// Run with `cargo run --example embed`, press q to quit.
+use color_eyre::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode},
+    layout::{Constraint, Layout},
+    style::Color,
+    widgets::{Block, Paragraph},
+};
+use termulator::{Scene, Vec3, Viewport3D, ViewportState};
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let mut terminal = ratatui::init();
+
+    let scene = Scene::default();
+    let mut state = ViewportState::default();
+    let mut angle: f64 = 0.0;
+
+    let result = loop {
+        // Orbit the camera around the cube
+        state.camera.yaw = -angle;
+        state.camera.pos =
+            Vec3::new(angle.to_radians().sin(), 0.3, -angle.to_radians().cos()) * 0.8;
+        state.camera.pitch = -20.0;
+
+        let drawn = terminal.draw(|frame| {
+            let [info, view] = Layout::horizontal([Constraint::Length(30), Constraint::Min(0)])
+                .areas(frame.area());
+
+            frame.render_widget(
+                Paragraph::new(format!("yaw: {:.0}\n\npress q to quit", angle))
+                    .block(Block::bordered().title("info")),
+                info,
+            );
+            frame.render_stateful_widget(
+                Viewport3D::new(&scene).background(Color::Black),
+                view,
+                &mut state,
+            );
+        });
+        if let Err(err) = drawn {
+            break Err(err.into());
+        }
+
+        if event::poll(std::time::Duration::from_millis(33))? {
+            if let Event::Key(key) = event::read()? {
+                if key.code == KeyCode::Char('q') {
+                    break Ok(());
+                }
+            }
+        }
+        angle = (angle + 2.0) % 360.0;
+    };
+
+    ratatui::restore();
+    result
+}
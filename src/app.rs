@@ -1,34 +1,270 @@
-use crate::camera::Camera;
-use crate::types::Vec2;
-use color_eyre::Result;
+use crate::console::{self, Console, ConsoleInput};
+use color_eyre::{eyre::eyre, Result};
 use crossterm::event;
 use crossterm::event::Event;
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, MouseButton, MouseEventKind};
+use ratatui::layout::Position;
+use ratatui::style::Color;
+use std::path::PathBuf;
+use std::time::Duration;
+use termulator::camera::{Camera, Projection, ViewPreset};
+use termulator::color::{ColorSupport, Dither};
+use termulator::edit::MeshEditor;
+use termulator::gizmo::{Axis, Gizmo, GizmoMode};
+use termulator::graphics::{GraphicsBackend, GraphicsProtocol};
+use termulator::headless::{self, Headless};
+use termulator::mesh::Mesh;
+use termulator::metrics;
+use termulator::output::DrawMode;
+use termulator::render::{HiddenLines, Shadows, SOFT_SHADOW_SAMPLES};
+use termulator::scene::{Scene, Transform};
+use termulator::stereo::{Stereo, StereoMode};
+use termulator::types::{Vec2, Vec3};
+use termulator::viewport::{RenderMode, Viewport3D, ViewportState};
 
 pub struct App {
     pub should_quit: bool,
     pub terminal_size: Vec2,
-    pub camera: Camera,
-    pub draw_mode: ratatui::symbols::Marker,
+    pub viewport: ViewportState,
+    pub draw_mode: DrawMode,
     pub render_mode: RenderMode,
+    pub hidden_lines: HiddenLines,
+    pub shadows: Shadows,
+    pub scene: Scene,
+    pub console: Console,
+    pub background: Color,
+    pub color_support: ColorSupport,
+    pub dither: Dither,
+    pub graphics: Option<GraphicsBackend>,
+    pub cell_size: Option<(usize, usize)>, // in pixels, when the terminal reports it
+    pub cell_aspect: f64,
+    pub frame_time: Duration,
+    pub fps: f64,
+    pub show_hud: bool,
+    pub show_inspector: bool,
+    pub selected_object: Option<usize>, // index into the scene objects
+    pub gizmo: Option<Gizmo>,
+    pub edit: Option<Edit>,
+    pub view: Option<ViewPreset>, // last one applied
+    pub quad: Option<Quad>,
+    pub stereo: Stereo,
+    drag: Option<Drag>,
+}
+
+// Editing the mesh of an object, moving vertices along one of its own axes
+pub struct Edit {
+    pub object: usize,
+    pub editor: MeshEditor,
+    pub axis: Axis,
+}
+
+// Four viewports like in a CAD tool: top, front and side drawings next to the
+// perspective camera. The active pane is the one in `App::viewport` and
+// `App::draw_mode`, so everything that moves the camera or changes the marker
+// works on it. Its own entry here is only updated when another one becomes
+// active.
+pub struct Quad {
+    pub panes: [Pane; 4],
+    pub active: usize,
+}
+
+pub struct Pane {
+    pub title: &'static str,
+    pub state: ViewportState,
+    pub draw_mode: DrawMode,
+}
+
+// An object being dragged with the gizmo, from where the mouse was pressed
+struct Drag {
+    column: u16,
+    row: u16,
+    object: usize,
+    transform: Transform, // before the drag
 }
 impl App {
     pub fn default() -> Self {
+        let cell_size = metrics::cell_pixel_size();
         Self {
             should_quit: false,
-            camera: Camera::default(),
-            terminal_size: Vec2 { x: 10.0, y: 10.0 },
-            draw_mode: ratatui::symbols::Marker::Braille,
+            viewport: ViewportState::default(),
+            terminal_size: terminal_size(),
+            draw_mode: DrawMode::Braille,
             render_mode: RenderMode::Vertex,
+            hidden_lines: HiddenLines::Show,
+            shadows: Shadows::Hard,
+            scene: Scene::default(),
+            console: Console::default(),
+            background: Color::Blue,
+            color_support: ColorSupport::detect(),
+            dither: Dither::Ordered,
+            // Without the pixel size of the cells the images can't be sized
+            graphics: GraphicsProtocol::detect()
+                .zip(cell_size)
+                .map(|(protocol, size)| GraphicsBackend::with_cell_size(protocol, size)),
+            cell_size,
+            cell_aspect: metrics::cell_aspect(cell_size),
+            frame_time: Duration::from_millis(500),
+            fps: 0.0,
+            show_hud: true,
+            show_inspector: true,
+            selected_object: None,
+            gizmo: None,
+            edit: None,
+            view: None,
+            quad: None,
+            stereo: Stereo::default(),
+            drag: None,
+        }
+    }
+
+    // Everything but the camera is shared by all viewports
+    pub fn viewport_widget(&self, draw_mode: DrawMode) -> Viewport3D<'_> {
+        let mut viewport = Viewport3D::new(&self.scene)
+            .draw_mode(draw_mode)
+            .render_mode(self.render_mode)
+            .hidden_lines(self.hidden_lines)
+            .shadows(self.shadows)
+            .background(self.background)
+            .color_support(self.color_support)
+            .dither(self.dither)
+            .cell_aspect(self.cell_aspect)
+            .selected(self.selected_object)
+            .gizmo(self.gizmo)
+            .stereo(self.stereo)
+            .edit(
+                self.edit
+                    .as_ref()
+                    .map(|edit| edit.editor.selection.as_slice()),
+            );
+        if draw_mode == DrawMode::Graphics {
+            if let Some(graphics) = &self.graphics {
+                viewport = viewport.backend(graphics);
+            }
+        }
+        viewport
+    }
+
+    // Switching from perspective keeps the scene center at about the same
+    // size on screen
+    pub fn set_projection(&mut self, projection: Projection) {
+        let (center, _) = self.framing();
+        let camera = &mut self.viewport.camera;
+        let parallel = Camera {
+            projection,
+            ..*camera
+        }
+        .is_parallel();
+        if camera.projection == Projection::Perspective && parallel {
+            let distance = (center - camera.pos).dot(camera.forward());
+            if distance > 0.0 {
+                camera.extent = (camera.fov.to_radians() / 2.0).tan() * distance;
+            }
+        }
+        camera.projection = projection;
+    }
+
+    pub fn apply_view(&mut self, view: ViewPreset) {
+        self.viewport.camera = self.view_camera(view);
+        self.view = Some(view);
+    }
+
+    // The camera fitting the selected object, or the whole scene, into a view
+    fn view_camera(&self, view: ViewPreset) -> Camera {
+        let (center, radius) = self.framing();
+        let mut camera = self.viewport.camera;
+        view.apply(&mut camera, center, (radius * 3.0).max(5.0));
+        camera.extent = (radius * 1.2).max(0.5);
+        camera
+    }
+
+    // The current camera becomes the perspective pane
+    pub fn set_quad(&mut self, quad: bool) {
+        if quad == self.quad.is_some() {
+            return;
+        }
+        if !quad {
+            self.quad = None;
+            return;
+        }
+        let pane = |title, camera| Pane {
+            title,
+            state: ViewportState::new(camera),
+            draw_mode: self.draw_mode,
+        };
+        self.quad = Some(Quad {
+            panes: [
+                pane("top", self.view_camera(ViewPreset::Top)),
+                pane("front", self.view_camera(ViewPreset::Front)),
+                pane("side", self.view_camera(ViewPreset::Side)),
+                pane("perspective", self.viewport.camera),
+            ],
+            active: 3,
+        });
+    }
+
+    pub fn activate_pane(&mut self, index: usize) {
+        let Some(quad) = &mut self.quad else {
+            return;
+        };
+        if index == quad.active {
+            return;
+        }
+        let previous = &mut quad.panes[quad.active];
+        previous.state = self.viewport.clone();
+        previous.draw_mode = self.draw_mode;
+        self.viewport = quad.panes[index].state.clone();
+        self.draw_mode = quad.panes[index].draw_mode;
+        quad.active = index;
+        self.drag = None;
+    }
+
+    // The pane a terminal cell was in during the last frame
+    fn pane_at(&self, column: u16, row: u16) -> Option<usize> {
+        let quad = self.quad.as_ref()?;
+        let position = Position::new(column, row);
+        if self.viewport.area.contains(position) {
+            return Some(quad.active);
         }
+        quad.panes
+            .iter()
+            .position(|pane| pane.state.area.contains(position))
+    }
+
+    // Center and radius of the bounding box of the selected object, or of all
+    // objects when nothing is selected
+    fn framing(&self) -> (Vec3, f64) {
+        let objects = match self.selected_object {
+            Some(index) => &self.scene.objects[index..=index],
+            None => &self.scene.objects[..],
+        };
+        let vertices: Vec<Vec3> = objects
+            .iter()
+            .flat_map(|object| object.world_vertices())
+            .collect();
+        let Some(&first) = vertices.first() else {
+            return (Vec3::new(0.0, 0.0, 0.0), 1.0);
+        };
+        let (min, max) = vertices.iter().fold((first, first), |(min, max), v| {
+            (
+                Vec3::new(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z)),
+                Vec3::new(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z)),
+            )
+        });
+        let center = (min + max) * 0.5;
+        let radius = vertices
+            .iter()
+            .map(|&v| (v - center).length())
+            .fold(0.0, f64::max);
+        (center, radius)
     }
 
-    pub fn get_aspect_ratio(&mut self) -> f64 {
-        self.terminal_size.y / self.terminal_size.x * 2.25 // *2.25 to adjust for difference in row and column width / height
+    // Whether the viewport is drawn as a bitmap instead of text cells
+    pub fn graphics_active(&self) -> bool {
+        self.draw_mode == DrawMode::Graphics && self.graphics.is_some()
     }
 
-    pub fn get_event(&self) -> Result<Option<Event>> {
-        if event::poll(core::time::Duration::from_millis(500))? {
+    pub fn get_event(&self, timeout: Duration) -> Result<Option<Event>> {
+        if event::poll(timeout)? {
             // It's guaranteed that the `read()` won't block when the `poll()`
             // function returns `true`
             Ok(Some(event::read()?))
@@ -46,9 +282,79 @@ impl App {
                     return Ok(Action::None);
                 }
 
+                if self.console.active {
+                    return Ok(match event.code {
+                        KeyCode::Char(c) => Action::Console(ConsoleInput::Char(c)),
+                        KeyCode::Backspace => Action::Console(ConsoleInput::Backspace),
+                        KeyCode::Tab => Action::Console(ConsoleInput::Complete),
+                        KeyCode::Up => Action::Console(ConsoleInput::HistoryPrevious),
+                        KeyCode::Down => Action::Console(ConsoleInput::HistoryNext),
+                        KeyCode::Enter => Action::Console(ConsoleInput::Submit),
+                        KeyCode::Esc => Action::Console(ConsoleInput::Close),
+                        _ => Action::None,
+                    });
+                }
+
                 match event.code {
                     KeyCode::Char(c) => match c {
                         'Q' => Ok(Action::Quit), // Quit app
+                        ':' => Ok(Action::OpenConsole),
+                        'h' => Ok(Action::ToggleHud),
+                        '[' => Ok(Action::SelectPrevious),
+                        ']' => Ok(Action::SelectNext),
+                        'g' => Ok(Action::ChangeGizmoMode(GizmoMode::Translate)),
+                        'r' => Ok(Action::ChangeGizmoMode(GizmoMode::Rotate)),
+                        'e' => Ok(Action::ChangeGizmoMode(GizmoMode::Scale)),
+                        'x' => Ok(Action::ChangeAxis(Axis::X)),
+                        'y' => Ok(Action::ChangeAxis(Axis::Y)),
+                        'z' => Ok(Action::ChangeAxis(Axis::Z)),
+                        'n' => Ok(Action::ToggleSnap),
+                        '+' | '=' => Ok(Action::Nudge(1.0)),
+                        '-' => Ok(Action::Nudge(-1.0)),
+                        'l' => Ok(Action::ChangeHiddenLines(match self.hidden_lines {
+                            HiddenLines::Show => HiddenLines::Hide,
+                            HiddenLines::Hide => HiddenLines::Dashed,
+                            HiddenLines::Dashed => HiddenLines::Show,
+                        })),
+                        'o' => Ok(Action::ChangeShadows(match self.shadows {
+                            Shadows::Off => Shadows::Hard,
+                            Shadows::Hard => Shadows::Soft(SOFT_SHADOW_SAMPLES),
+                            Shadows::Soft(_) => Shadows::Off,
+                        })),
+                        'm' => Ok(Action::ToggleEdit),
+                        'c' => Ok(Action::Edit(EditOperation::Connect)),
+                        'X' => Ok(Action::Edit(EditOperation::Disconnect)),
+                        'E' => Ok(Action::Edit(EditOperation::Extrude)),
+                        'M' => Ok(Action::Edit(EditOperation::Merge)),
+                        'u' => Ok(Action::Edit(EditOperation::Undo)),
+                        'U' => Ok(Action::Edit(EditOperation::Redo)),
+                        'p' => Ok(Action::ChangeProjection(
+                            match self.viewport.camera.projection {
+                                Projection::Perspective => Projection::Orthographic,
+                                Projection::Orthographic => Projection::Oblique(1.0),
+                                Projection::Oblique(1.0) => Projection::Oblique(0.5),
+                                Projection::Oblique(_) => Projection::Fisheye(180.0),
+                                Projection::Fisheye(_) => Projection::Equirectangular,
+                                Projection::Equirectangular => Projection::Perspective,
+                            },
+                        )),
+                        'V' => Ok(Action::ToggleQuad),
+                        'S' => Ok(Action::ChangeStereo(match self.stereo.mode {
+                            StereoMode::Off => StereoMode::Anaglyph,
+                            StereoMode::Anaglyph => StereoMode::SideBySide,
+                            StereoMode::SideBySide => StereoMode::CrossEyed,
+                            StereoMode::CrossEyed => StereoMode::Off,
+                        })),
+                        ',' => Ok(Action::Converge(1.0 / 1.1)),
+                        '.' => Ok(Action::Converge(1.1)),
+                        'v' => Ok(Action::ChangeView(match self.view {
+                            Some(view) => {
+                                let index = ViewPreset::ALL.iter().position(|&v| v == view);
+                                ViewPreset::ALL
+                                    [index.map_or(0, |i| (i + 1) % ViewPreset::ALL.len())]
+                            }
+                            None => ViewPreset::ALL[0],
+                        })),
 
                         'a' => Ok(Action::Move(Direction::Left)),
                         'd' => Ok(Action::Move(Direction::Right)),
@@ -64,18 +370,28 @@ impl App {
                     },
 
                     KeyCode::F(n) => match n {
-                        1 => Ok(Action::ChangeDrawMode(ratatui::symbols::Marker::Braille)),
-                        2 => Ok(Action::ChangeDrawMode(ratatui::symbols::Marker::Dot)),
-                        3 => Ok(Action::ChangeDrawMode(ratatui::symbols::Marker::HalfBlock)),
-                        4 => Ok(Action::ChangeDrawMode(ratatui::symbols::Marker::Block)),
-                        5 => Ok(Action::ChangeDrawMode(ratatui::symbols::Marker::Bar)),
+                        1 => Ok(Action::ChangeDrawMode(DrawMode::Braille)),
+                        2 => Ok(Action::ChangeDrawMode(DrawMode::Dot)),
+                        3 => Ok(Action::ChangeDrawMode(DrawMode::HalfBlock)),
+                        4 => Ok(Action::ChangeDrawMode(DrawMode::Block)),
+                        5 => Ok(Action::ChangeDrawMode(DrawMode::Bar)),
+                        6 => Ok(Action::ChangeDrawMode(DrawMode::Ascii)),
+                        7 => Ok(Action::ChangeDrawMode(DrawMode::Pixels)),
 
                         8 => Ok(Action::ChangeRenderMode(RenderMode::Vertex)),
                         9 => Ok(Action::ChangeRenderMode(RenderMode::Raycast)),
+                        10 => Ok(Action::ChangeRenderMode(RenderMode::Raymarch)),
+                        11 => Ok(Action::ChangeRenderMode(RenderMode::PathTrace)),
+                        12 => Ok(Action::ChangeRenderMode(RenderMode::Solid)),
 
                         _ => Ok(Action::None),
                     },
 
+                    KeyCode::Tab => Ok(match &self.quad {
+                        Some(quad) => Action::ActivatePane((quad.active + 1) % quad.panes.len()),
+                        None => Action::ToggleInspector,
+                    }),
+
                     KeyCode::Left => Ok(Action::Look(Direction::Left)),
                     KeyCode::Right => Ok(Action::Look(Direction::Right)),
                     KeyCode::Up => Ok(Action::Look(Direction::Up)),
@@ -86,12 +402,22 @@ impl App {
                     }
                 }
             }
-            Some(Event::Mouse(_event)) => Ok(Action::None),
+            Some(Event::Mouse(event)) => match event.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    Ok(Action::Pick(event.column, event.row))
+                }
+                MouseEventKind::Drag(MouseButton::Left) => {
+                    Ok(Action::Drag(event.column, event.row))
+                }
+                MouseEventKind::Up(MouseButton::Left) => Ok(Action::EndDrag),
+                MouseEventKind::ScrollUp => Ok(Action::Zoom(0.9)),
+                MouseEventKind::ScrollDown => Ok(Action::Zoom(1.1)),
+                _ => Ok(Action::None),
+            },
             Some(Event::Paste(_string)) => Ok(Action::None),
-            Some(Event::Resize(x, y)) => Ok(Action::ChangeWindowSize(Vec2 {
-                x: x as f64,
-                y: y as f64,
-            })),
+            Some(Event::Resize(x, y)) => {
+                Ok(Action::ChangeWindowSize(Vec2::new(x as f64, y as f64)))
+            }
             _ => Ok(Action::None),
         }
     }
@@ -101,50 +427,302 @@ impl App {
             Action::Quit => self.should_quit = true,
             Action::Move(direction) => match direction {
                 Direction::Forward => {
-                    self.camera.pos =
-                        self.camera.pos + self.camera.forward_movement() * self.camera.move_speed
+                    self.viewport.camera.pos = self.viewport.camera.pos
+                        + self.viewport.camera.forward_movement() * self.viewport.camera.move_speed
                 }
                 Direction::Backward => {
-                    self.camera.pos =
-                        self.camera.pos - self.camera.forward_movement() * self.camera.move_speed
+                    self.viewport.camera.pos = self.viewport.camera.pos
+                        - self.viewport.camera.forward_movement() * self.viewport.camera.move_speed
                 }
                 Direction::Left => {
-                    self.camera.pos = self.camera.pos - self.camera.right() * self.camera.move_speed
+                    self.viewport.camera.pos = self.viewport.camera.pos
+                        - self.viewport.camera.right() * self.viewport.camera.move_speed
                 }
                 Direction::Right => {
-                    self.camera.pos = self.camera.pos + self.camera.right() * self.camera.move_speed
+                    self.viewport.camera.pos = self.viewport.camera.pos
+                        + self.viewport.camera.right() * self.viewport.camera.move_speed
                 }
                 Direction::Up => {
-                    self.camera.pos.y = self.camera.pos.y + self.camera.move_speed;
+                    self.viewport.camera.pos.y += self.viewport.camera.move_speed;
                 }
                 Direction::Down => {
-                    self.camera.pos.y = self.camera.pos.y - self.camera.move_speed;
+                    self.viewport.camera.pos.y -= self.viewport.camera.move_speed;
                 }
             },
             Action::Look(direction) => match direction {
                 Direction::Up => {
-                    self.camera.pitch += self.camera.rotate_speed;
-                    self.camera.pitch = self.camera.pitch.clamp(-89.0, 89.0);
+                    self.viewport.camera.pitch += self.viewport.camera.rotate_speed;
+                    self.viewport.camera.pitch = self.viewport.camera.pitch.clamp(-89.0, 89.0);
                 }
                 Direction::Down => {
-                    self.camera.pitch -= self.camera.rotate_speed;
-                    self.camera.pitch = self.camera.pitch.clamp(-89.0, 89.0);
+                    self.viewport.camera.pitch -= self.viewport.camera.rotate_speed;
+                    self.viewport.camera.pitch = self.viewport.camera.pitch.clamp(-89.0, 89.0);
                 }
                 Direction::Left => {
-                    self.camera.yaw -= self.camera.rotate_speed;
+                    self.viewport.camera.yaw -= self.viewport.camera.rotate_speed;
                 }
                 Direction::Right => {
-                    self.camera.yaw += self.camera.rotate_speed;
+                    self.viewport.camera.yaw += self.viewport.camera.rotate_speed;
                 }
                 _ => (), // Skip forward and backward
             },
 
-            Action::ChangeDrawMode(mode) => self.draw_mode = mode,
+            Action::ChangeDrawMode(mode) => {
+                if mode == DrawMode::Graphics && self.graphics.is_none() {
+                    self.console
+                        .info("no graphics support detected, drawing braille instead");
+                }
+                self.draw_mode = mode;
+            }
             Action::ChangeRenderMode(mode) => self.render_mode = mode,
+            Action::ChangeHiddenLines(hidden_lines) => self.hidden_lines = hidden_lines,
+            Action::ChangeShadows(shadows) => self.shadows = shadows,
+            Action::ChangeColorSupport(color_support) => self.color_support = color_support,
+            Action::ChangeDither(dither) => self.dither = dither,
+
+            Action::ChangeWindowSize(size) => self.terminal_size = size,
+
+            Action::ChangeProjection(projection) => self.set_projection(projection),
+            Action::ChangeView(view) => self.apply_view(view),
+            Action::Zoom(factor) => {
+                let camera = &mut self.viewport.camera;
+                match camera.projection {
+                    Projection::Perspective => {
+                        camera.fov = (camera.fov * factor).clamp(10.0, 150.0)
+                    }
+                    Projection::Orthographic | Projection::Oblique(_) => {
+                        camera.extent = (camera.extent * factor).max(0.01)
+                    }
+                    Projection::Fisheye(fov) => {
+                        camera.projection = Projection::Fisheye((fov * factor).clamp(10.0, 360.0))
+                    }
+                    // Always shows everything
+                    Projection::Equirectangular => (),
+                }
+            }
+            Action::SetExtent(extent) => self.viewport.camera.extent = extent,
+            Action::SetFov(fov) => self.viewport.camera.fov = fov,
+            Action::Teleport(pos) => self.viewport.camera.pos = pos,
+            Action::SetMoveSpeed(speed) => self.viewport.camera.move_speed = speed,
+            Action::Load(path) => match Scene::load(&path) {
+                Ok(scene) => {
+                    self.console.info(format!(
+                        "loaded {} ({} objects, {} vertices)",
+                        path.display(),
+                        scene.objects.len(),
+                        scene
+                            .objects
+                            .iter()
+                            .map(|o| o.mesh.vertices.len())
+                            .sum::<usize>()
+                    ));
+                    self.scene = scene;
+                    self.selected_object = None;
+                    self.edit = None;
+                    self.drag = None;
+                }
+                Err(err) => self.console.error(format!("{:#}", err)),
+            },
+
+            Action::ChangeStereo(mode) => self.stereo.mode = mode,
+            Action::SetEyeDistance(distance) => self.stereo.eye_distance = distance,
+            Action::Converge(factor) => {
+                self.stereo.convergence = (self.stereo.convergence * factor).clamp(0.05, 1000.0)
+            }
+            Action::SetConvergence(distance) => self.stereo.convergence = distance,
+            Action::ToggleQuad => self.set_quad(self.quad.is_none()),
+            Action::SetQuad(quad) => self.set_quad(quad),
+            Action::ActivatePane(index) => self.activate_pane(index),
+            Action::ToggleHud => self.show_hud = !self.show_hud,
+            Action::ToggleInspector => self.show_inspector = !self.show_inspector,
+            Action::SelectNext => {
+                self.edit = None;
+                let count = self.scene.objects.len();
+                self.selected_object = match self.selected_object {
+                    _ if count == 0 => None,
+                    Some(index) => Some((index + 1) % count),
+                    None => Some(0),
+                };
+            }
+            Action::SelectPrevious => {
+                self.edit = None;
+                let count = self.scene.objects.len();
+                self.selected_object = match self.selected_object {
+                    _ if count == 0 => None,
+                    Some(index) => Some((index + count - 1) % count),
+                    None => Some(count - 1),
+                };
+            }
+            Action::Pick(column, row) => {
+                if let Some(pane) = self.pane_at(column, row) {
+                    self.activate_pane(pane);
+                }
+                if let Some(edit) = &mut self.edit {
+                    let object = &self.scene.objects[edit.object];
+                    if let Some(vertex) = self.viewport.pick_vertex(object, column, row) {
+                        edit.editor.toggle(vertex);
+                    }
+                    return;
+                }
+                self.selected_object = self.viewport.pick(&self.scene, column, row);
+                if let (Some(_), Some(object)) = (self.gizmo, self.selected_object) {
+                    self.drag = Some(Drag {
+                        column,
+                        row,
+                        object,
+                        transform: self.scene.objects[object].transform,
+                    });
+                }
+            }
+            Action::Drag(column, row) => {
+                let (Some(gizmo), Some(drag)) = (self.gizmo, &self.drag) else {
+                    return;
+                };
+                let object = &mut self.scene.objects[drag.object];
+                object.transform = drag.transform;
+                let amount = gizmo.drag_amount(
+                    object,
+                    &self.viewport.camera,
+                    self.viewport.to_ndc(drag.column, drag.row),
+                    self.viewport.to_ndc(column, row),
+                );
+                gizmo.apply(&mut object.transform, amount);
+            }
+            Action::EndDrag => self.drag = None,
+
+            Action::ChangeGizmoMode(mode) => {
+                self.gizmo = match self.gizmo {
+                    Some(gizmo) if gizmo.mode == mode => None,
+                    Some(gizmo) => Some(Gizmo { mode, ..gizmo }),
+                    None => Some(Gizmo {
+                        mode,
+                        ..Gizmo::default()
+                    }),
+                };
+                self.edit = None;
+                self.drag = None;
+            }
+            Action::ChangeAxis(axis) => {
+                if let Some(edit) = &mut self.edit {
+                    edit.axis = axis;
+                } else if let Some(gizmo) = &mut self.gizmo {
+                    gizmo.axis = axis;
+                }
+            }
+            Action::ToggleSnap => {
+                if let Some(gizmo) = &mut self.gizmo {
+                    gizmo.snap = !gizmo.snap;
+                }
+            }
+            Action::Nudge(direction) => {
+                if let Some(edit) = &mut self.edit {
+                    let mesh = &mut self.scene.objects[edit.object].mesh;
+                    let offset = edit.axis.unit() * (direction * edit_step(mesh));
+                    if let Err(err) = edit.editor.move_selected(mesh, offset) {
+                        self.console.error(err.to_string());
+                    }
+                    return;
+                }
+                let (Some(gizmo), Some(index)) = (self.gizmo, self.selected_object) else {
+                    return;
+                };
+                if let Some(object) = self.scene.objects.get_mut(index) {
+                    gizmo.apply(&mut object.transform, direction * gizmo.step());
+                }
+            }
+            Action::ToggleEdit => {
+                if self.edit.take().is_some() {
+                    return;
+                }
+                match self.selected_object {
+                    Some(object) => {
+                        self.gizmo = None;
+                        self.drag = None;
+                        self.edit = Some(Edit {
+                            object,
+                            editor: MeshEditor::default(),
+                            axis: Axis::X,
+                        });
+                    }
+                    None => self.console.error("select an object to edit its mesh"),
+                }
+            }
+            Action::Edit(operation) => {
+                let Some(edit) = &mut self.edit else {
+                    return;
+                };
+                let mesh = &mut self.scene.objects[edit.object].mesh;
+                let editor = &mut edit.editor;
+                let result = match operation {
+                    EditOperation::Connect => editor.add_edge(mesh),
+                    EditOperation::Disconnect => editor.delete_edge(mesh),
+                    EditOperation::Extrude => {
+                        editor.extrude(mesh, edit.axis.unit() * edit_step(mesh))
+                    }
+                    EditOperation::Merge => editor.merge(mesh),
+                    EditOperation::Undo => match editor.undo(mesh) {
+                        true => Ok(()),
+                        false => Err(eyre!("nothing to undo")),
+                    },
+                    EditOperation::Redo => match editor.redo(mesh) {
+                        true => Ok(()),
+                        false => Err(eyre!("nothing to redo")),
+                    },
+                };
+                if let Err(err) = result {
+                    self.console.error(err.to_string());
+                }
+            }
+            Action::Export(path) => {
+                let Some(object) = self
+                    .selected_object
+                    .and_then(|i| self.scene.objects.get_mut(i))
+                else {
+                    self.console.error("select an object to export its mesh");
+                    return;
+                };
+                match object.mesh.save_obj(&path) {
+                    Ok(()) => {
+                        // Saving the scene refers to the exported file from now on
+                        object.mesh.path = Some(std::path::absolute(&path).unwrap_or(path.clone()));
+                        self.console.info(format!(
+                            "exported {} to {}",
+                            object.name,
+                            path.display()
+                        ));
+                    }
+                    Err(err) => self.console.error(format!("{:#}", err)),
+                }
+            }
+            Action::Render(headless) => {
+                let start = std::time::Instant::now();
+                let mut state = self.viewport.clone();
+                // Files aren't limited to the colors of the terminal, like --output
+                let viewport = self
+                    .viewport_widget(self.draw_mode)
+                    .color_support(ColorSupport::TrueColor);
+                match headless::render_to_file(&headless, viewport, &mut state) {
+                    Ok(()) => self.console.info(format!(
+                        "rendered {} in {:.1}s",
+                        headless.output.display(),
+                        start.elapsed().as_secs_f64()
+                    )),
+                    Err(err) => self.console.error(format!("{:#}", err)),
+                }
+            }
+            Action::Save(path) => match self.scene.save_scene(&path) {
+                Ok(()) => self.console.info(format!("saved {}", path.display())),
+                Err(err) => self.console.error(format!("{:#}", err)),
+            },
 
-            Action::ChangeWindowSize(size) => {
-                self.terminal_size = size;
-                self.camera.aspect_ratio = self.get_aspect_ratio()
+            Action::OpenConsole => self.console.open(),
+            Action::Console(input) => {
+                if let Some(line) = self.console.handle_input(input) {
+                    match console::parse_command(&line) {
+                        Ok(action) => self.process_action(action),
+                        Err(err) => self.console.error(err),
+                    }
+                }
             }
 
             Action::None => (),
@@ -156,12 +734,58 @@ pub enum Action {
     Quit,
     Move(Direction),
     Look(Direction),
-    ChangeDrawMode(ratatui::symbols::Marker),
+    ChangeDrawMode(DrawMode),
     ChangeRenderMode(RenderMode),
+    ChangeHiddenLines(HiddenLines),
+    ChangeShadows(Shadows),
+    ChangeProjection(Projection),
+    ChangeView(ViewPreset),
+    Zoom(f64),      // scales the field of view or extent, if there is one
+    SetExtent(f64), // of parallel projections
+    ChangeColorSupport(ColorSupport),
+    ChangeDither(Dither),
     ChangeWindowSize(Vec2),
+    SetFov(f64),
+    Teleport(Vec3),
+    SetMoveSpeed(f64),
+    Load(PathBuf),
+    ChangeStereo(StereoMode),
+    SetEyeDistance(f64),
+    Converge(f64), // scales the convergence distance
+    SetConvergence(f64),
+    ToggleQuad,
+    SetQuad(bool),
+    ActivatePane(usize), // of the quad view
+    ToggleHud,
+    ToggleInspector,
+    SelectNext,
+    SelectPrevious,
+    Pick(u16, u16), // terminal cell
+    Drag(u16, u16),
+    EndDrag,
+    ChangeGizmoMode(GizmoMode),
+    ChangeAxis(Axis), // of the gizmo or the mesh being edited
+    ToggleSnap,
+    Nudge(f64), // by one step in this direction
+    Save(PathBuf),
+    ToggleEdit,
+    Edit(EditOperation),
+    Export(PathBuf),
+    Render(Headless), // the current view to a file
+    OpenConsole,
+    Console(ConsoleInput),
     None,
 }
 
+pub enum EditOperation {
+    Connect,
+    Disconnect,
+    Extrude,
+    Merge,
+    Undo,
+    Redo,
+}
+
 pub enum Direction {
     Forward,
     Backward,
@@ -171,8 +795,19 @@ pub enum Direction {
     Down,
 }
 
-#[derive(Debug)]
-pub enum RenderMode {
-    Vertex,
-    Raycast,
+// Vertices move by a tenth of the size of the mesh
+fn edit_step(mesh: &Mesh) -> f64 {
+    let extent = |axis: fn(&Vec3) -> f64| {
+        let values = mesh.vertices.iter().map(axis);
+        values.clone().fold(f64::NEG_INFINITY, f64::max) - values.fold(f64::INFINITY, f64::min)
+    };
+    let size = extent(|v| v.x).max(extent(|v| v.y)).max(extent(|v| v.z));
+    (size / 10.0).max(0.01)
+}
+
+// The size before the first resize event, assuming the usual 80x24 when the
+// terminal can't be asked
+fn terminal_size() -> Vec2 {
+    let (columns, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+    Vec2::new(columns as f64, rows as f64)
 }
@@ -1,10 +1,85 @@
 use crate::types::Vec3;
-#[derive(Debug)]
+
+use std::f64::consts::{FRAC_PI_2, PI};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+    // Parallel lines along the view direction recede at 45 degrees, shortened
+    // by this factor: 1 for cavalier, 0.5 for cabinet
+    Oblique(f64),
+    // The angle from the view direction grows linearly towards the edge of a
+    // circle as high as the view, which spans this field of view in degrees
+    // (up to 360)
+    Fisheye(f64),
+    // Longitude across the whole width and latitude up the whole height
+    Equirectangular,
+}
+
+// Views that set the direction, and for drawings the projection, of a camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewPreset {
+    Isometric,
+    Dimetric,
+    Cavalier,
+    Cabinet,
+    Top,
+    Front,
+    Side,
+}
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 7] = [
+        ViewPreset::Isometric,
+        ViewPreset::Dimetric,
+        ViewPreset::Cavalier,
+        ViewPreset::Cabinet,
+        ViewPreset::Top,
+        ViewPreset::Front,
+        ViewPreset::Side,
+    ];
+
+    // Looks at `target` from `distance` away
+    pub fn apply(self, camera: &mut Camera, target: Vec3, distance: f64) {
+        let (projection, yaw, pitch) = match self {
+            // Every axis is shortened by the same amount
+            ViewPreset::Isometric => (
+                Projection::Orthographic,
+                45.0,
+                -(1.0 / 2.0f64.sqrt()).atan().to_degrees(),
+            ),
+            // Edges along x and z rise one pixel for every two across
+            ViewPreset::Dimetric => (Projection::Orthographic, 45.0, -30.0),
+            ViewPreset::Cavalier => (Projection::Oblique(1.0), 0.0, 0.0),
+            ViewPreset::Cabinet => (Projection::Oblique(0.5), 0.0, 0.0),
+            ViewPreset::Top => (Projection::Orthographic, 0.0, -90.0),
+            ViewPreset::Front => (Projection::Orthographic, 0.0, 0.0),
+            ViewPreset::Side => (Projection::Orthographic, -90.0, 0.0),
+        };
+        camera.projection = projection;
+        camera.yaw = yaw;
+        camera.pitch = pitch;
+        camera.pos = target - camera.forward() * distance;
+
+        // Oblique views shift everything by its depth, this brings the
+        // target back to the center
+        if let Projection::Oblique(depth) = projection {
+            let (shift_x, shift_y) = Camera::oblique_shift(depth);
+            camera.pos =
+                camera.pos + camera.camera_to_world(Vec3::new(shift_x, shift_y, 0.0)) * distance;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Camera {
     pub pos: Vec3,
     pub fov: f64,
     pub aspect_ratio: f64,
 
+    pub projection: Projection,
+    pub extent: f64, // half the height of the view in world units, unless perspective
+
     pub near_plane: f64,
     pub far_plane: f64,
 
@@ -14,13 +89,16 @@ pub struct Camera {
     pub move_speed: f64,
     pub rotate_speed: f64,
 }
-impl Camera {
-    pub fn default() -> Self {
+impl Default for Camera {
+    fn default() -> Self {
         Self {
             pos: Vec3::new(0.0, 0.0, -1.0),
             fov: 45.0,
             aspect_ratio: 16.0 / 9.0,
 
+            projection: Projection::Perspective,
+            extent: 1.0,
+
             near_plane: 0.1,
             far_plane: 100.0,
 
@@ -31,8 +109,9 @@ impl Camera {
             rotate_speed: 5.0,
         }
     }
-
-    fn forward(&self) -> Vec3 {
+}
+impl Camera {
+    pub fn forward(&self) -> Vec3 {
         let yaw_rad = self.yaw.to_radians();
         let pitch_rad = self.pitch.to_radians();
 
@@ -65,23 +144,106 @@ impl Camera {
         // first apply view transformation
         let view_space = self.apply_view_transform(*vertex - self.pos);
 
+        self.project_view_point(view_space)
+    }
+
+    pub fn project_view_point(&self, view_space: Vec3) -> (f64, f64) {
+        if self.is_panoramic() {
+            return self.project_panoramic(view_space);
+        }
+
         // we dont want to project points behind the camera
         if view_space.z <= 0.0 {
             return (10.0, 10.0); // Place points behind camera off-screen
         }
 
-        // Now do perspective projection
-        let scale = (self.fov / 2.0).to_radians().tan();
+        let (x, y) = match self.projection {
+            Projection::Perspective => {
+                let scale = (self.fov / 2.0).to_radians().tan();
+                (
+                    view_space.x / (scale * view_space.z),
+                    view_space.y / (scale * view_space.z),
+                )
+            }
+            Projection::Orthographic => (view_space.x / self.extent, view_space.y / self.extent),
+            Projection::Oblique(depth) => {
+                let (shift_x, shift_y) = Self::oblique_shift(depth);
+                (
+                    (view_space.x + view_space.z * shift_x) / self.extent,
+                    (view_space.y + view_space.z * shift_y) / self.extent,
+                )
+            }
+            Projection::Fisheye(_) | Projection::Equirectangular => unreachable!(),
+        };
+        (x * self.aspect_ratio, y)
+    }
+
+    // Points all around the camera land on screen, only the camera itself
+    // has no direction
+    fn project_panoramic(&self, view_space: Vec3) -> (f64, f64) {
+        let length = view_space.length();
+        if length == 0.0 {
+            return (10.0, 10.0);
+        }
+        let direction = view_space * (1.0 / length);
+        match self.projection {
+            Projection::Fisheye(fov) => {
+                let angle = direction.z.clamp(-1.0, 1.0).acos();
+                let radius = angle / (fov / 2.0).to_radians();
+                let around = direction.x.hypot(direction.y);
+                if around == 0.0 {
+                    // straight ahead, or straight behind on the rim
+                    return (0.0, if direction.z > 0.0 { 0.0 } else { -radius });
+                }
+                (
+                    direction.x / around * radius * self.aspect_ratio,
+                    direction.y / around * radius,
+                )
+            }
+            _ => (
+                direction.x.atan2(direction.z) / PI,
+                direction.y.clamp(-1.0, 1.0).asin() / FRAC_PI_2,
+            ),
+        }
+    }
+
+    // Distance along the view direction, or from the camera itself for
+    // panoramic projections, which see behind it too
+    pub fn depth(&self, view_space: Vec3) -> f64 {
+        if self.is_panoramic() {
+            view_space.length()
+        } else {
+            view_space.z
+        }
+    }
+
+    // How far a point moves up and right on screen for every unit it is
+    // further away
+    fn oblique_shift(depth: f64) -> (f64, f64) {
+        let angle = 45f64.to_radians();
+        (depth * angle.cos(), depth * angle.sin())
+    }
 
-        let x = (view_space.x / (scale * view_space.z)) * self.aspect_ratio;
-        let y = view_space.y / (scale * view_space.z);
-        (x, y)
+    // Projections other than perspective keep parallel lines parallel
+    pub fn is_parallel(&self) -> bool {
+        matches!(
+            self.projection,
+            Projection::Orthographic | Projection::Oblique(_)
+        )
+    }
+
+    // Projections that bend straight lines
+    pub fn is_panoramic(&self) -> bool {
+        matches!(
+            self.projection,
+            Projection::Fisheye(_) | Projection::Equirectangular
+        )
     }
 
     pub fn apply_view_transform(&self, point: Vec3) -> Vec3 {
         // create rotation matrices for yaw and pitch
         let yaw_rad = -self.yaw.to_radians();
-        let pitch_rad = -self.pitch.to_radians();
+        let pitch_rad = self.pitch.to_radians();
 
         // First rotate around Y axis (yaw)
         let mut result = Vec3 {
@@ -100,7 +262,7 @@ impl Camera {
         result
     }
 
-    pub fn _clip_line_to_near_plane(&self, v1: Vec3, v2: Vec3) -> Option<(Vec3, Vec3)> {
+    pub fn clip_line_to_near_plane(&self, v1: Vec3, v2: Vec3) -> Option<(Vec3, Vec3)> {
         // Check if both points are behind the camera
         if v1.z <= self.near_plane && v2.z <= self.near_plane {
             return None;
@@ -120,74 +282,389 @@ impl Camera {
         };
 
         if v1.z > self.near_plane {
-            return Some((v1, intersection));
+            Some((v1, intersection))
         } else {
-            return Some((intersection, v2));
+            Some((intersection, v2))
         }
     }
 
     pub fn cast_ray(&self, u: f64, v: f64) -> Vec3 {
         let fov_rad = (self.fov.to_radians() / 2.0).tan();
 
-        let x = u * fov_rad * self.aspect_ratio;
+        // inverse of the projection, which scales x by the aspect ratio
+        let x = u * fov_rad / self.aspect_ratio;
         let y = v * fov_rad;
         let ray_camera = Vec3::new(x, y, 1.0).normalize();
 
         self.camera_to_world(ray_camera)
     }
 
-    pub fn check_ray_aabb_intersections(&self, ray_direction: Vec3, min: Vec3, max: Vec3) -> bool {
-        let inv_dir = Vec3 {
-            x: 1.0 / ray_direction.x,
-            y: 1.0 / ray_direction.y,
-            z: 1.0 / ray_direction.z,
-        };
+    // The origin and direction of the ray through a point of the screen in
+    // normalized device coordinates. Parallel projections cast every ray in
+    // the same direction, from a point on the plane of the camera. None
+    // outside the circle of a fisheye that sees all around.
+    pub fn ray(&self, u: f64, v: f64) -> Option<(Vec3, Vec3)> {
+        let (x, y) = (u / self.aspect_ratio * self.extent, v * self.extent);
+        Some(match self.projection {
+            Projection::Perspective => (self.pos, self.cast_ray(u, v)),
+            Projection::Orthographic => (
+                self.pos + self.camera_to_world(Vec3::new(x, y, 0.0)),
+                self.forward().normalize(),
+            ),
+            Projection::Oblique(depth) => {
+                let (shift_x, shift_y) = Self::oblique_shift(depth);
+                (
+                    self.pos + self.camera_to_world(Vec3::new(x, y, 0.0)),
+                    self.camera_to_world(Vec3::new(-shift_x, -shift_y, 1.0))
+                        .normalize(),
+                )
+            }
+            Projection::Fisheye(fov) => {
+                let (x, y) = (u / self.aspect_ratio, v);
+                let radius = x.hypot(y);
+                let angle = radius * (fov / 2.0).to_radians();
+                if angle > PI {
+                    return None;
+                }
+                let (sin, cos) = angle.sin_cos();
+                let direction = if radius == 0.0 {
+                    Vec3::new(0.0, 0.0, 1.0)
+                } else {
+                    Vec3::new(x / radius * sin, y / radius * sin, cos)
+                };
+                (self.pos, self.camera_to_world(direction))
+            }
+            Projection::Equirectangular => {
+                let (longitude, latitude) = (u * PI, v * FRAC_PI_2);
+                let direction = Vec3::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                );
+                (self.pos, self.camera_to_world(direction))
+            }
+        })
+    }
 
-        let mut tmin = (min.x - self.pos.x) * inv_dir.x;
-        let mut tmax = (max.x - self.pos.x) * inv_dir.x;
-        if tmin > tmax {
-            std::mem::swap(&mut tmin, &mut tmax);
+    // The ray that ends in a point, from the plane of the camera or the
+    // camera itself
+    pub fn ray_to(&self, point: Vec3) -> (Vec3, Vec3) {
+        let view = self.apply_view_transform(point - self.pos);
+        match self.projection {
+            Projection::Perspective | Projection::Fisheye(_) | Projection::Equirectangular => {
+                (self.pos, (point - self.pos).normalize())
+            }
+            Projection::Orthographic => {
+                let direction = self.forward().normalize();
+                (point - direction * view.z, direction)
+            }
+            Projection::Oblique(depth) => {
+                let (shift_x, shift_y) = Self::oblique_shift(depth);
+                let direction = self.camera_to_world(Vec3::new(-shift_x, -shift_y, 1.0));
+                (point - direction * view.z, direction.normalize())
+            }
         }
+    }
+
+    pub fn check_ray_aabb_intersections(&self, ray_direction: Vec3, min: Vec3, max: Vec3) -> bool {
+        ray_hits_aabb(self.pos, ray_direction, min, max)
+    }
+
+    pub fn camera_to_world(&self, dir: Vec3) -> Vec3 {
+        let forward = self.forward().normalize();
+        let right = self.right().normalize();
+        let up = forward.cross(right).normalize();
 
-        let mut tymin = (min.y - self.pos.y) * inv_dir.y;
-        let mut tymax = (max.y - self.pos.y) * inv_dir.y;
-        if tymin > tymax {
-            std::mem::swap(&mut tymin, &mut tymax);
+        Vec3 {
+            x: dir.x * right.x + dir.y * up.x + dir.z * forward.x,
+            y: dir.x * right.y + dir.y * up.y + dir.z * forward.y,
+            z: dir.x * right.z + dir.y * up.z + dir.z * forward.z,
         }
+    }
+}
+
+// Slab test, whether a ray from `origin` passes through a box
+pub fn ray_hits_aabb(origin: Vec3, ray_direction: Vec3, min: Vec3, max: Vec3) -> bool {
+    let inv_dir = Vec3 {
+        x: 1.0 / ray_direction.x,
+        y: 1.0 / ray_direction.y,
+        z: 1.0 / ray_direction.z,
+    };
+
+    let mut tmin = (min.x - origin.x) * inv_dir.x;
+    let mut tmax = (max.x - origin.x) * inv_dir.x;
+    if tmin > tmax {
+        std::mem::swap(&mut tmin, &mut tmax);
+    }
+
+    let mut tymin = (min.y - origin.y) * inv_dir.y;
+    let mut tymax = (max.y - origin.y) * inv_dir.y;
+    if tymin > tymax {
+        std::mem::swap(&mut tymin, &mut tymax);
+    }
 
-        if (tmin > tymax) || (tymin > tmax) {
-            return false;
+    if (tmin > tymax) || (tymin > tmax) {
+        return false;
+    }
+
+    if tymin > tmin {
+        tmin = tymin;
+    }
+    if tymax < tmax {
+        tmax = tymax;
+    }
+
+    let mut tzmin = (min.z - origin.z) * inv_dir.z;
+    let mut tzmax = (max.z - origin.z) * inv_dir.z;
+    if tzmin > tzmax {
+        std::mem::swap(&mut tzmin, &mut tzmax);
+    }
+
+    if (tmin > tzmax) || (tzmin > tmax) {
+        return false;
+    }
+
+    if tzmax < tmax {
+        tmax = tzmax;
+    }
+
+    // the box is behind the camera
+    tmax >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-6 && (actual.1 - expected.1).abs() < 1e-6,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    fn camera(yaw: f64, pitch: f64) -> Camera {
+        Camera {
+            pos: Vec3::new(1.0, -2.0, 3.0),
+            yaw,
+            pitch,
+            ..Default::default()
         }
+    }
+
+    #[test]
+    fn project_point_straight_ahead_to_center() {
+        let camera = Camera::default();
+        let point = camera.pos + Vec3::new(0.0, 0.0, 5.0);
+        assert_close(camera.project_vertex(&point), (0.0, 0.0));
+    }
+
+    #[test]
+    fn project_keeps_orientation() {
+        let camera = Camera::default();
+        let (x, y) = camera.project_vertex(&(camera.pos + Vec3::new(0.5, 0.5, 5.0)));
+        assert!(x > 0.0, "right of the camera should be right on screen");
+        assert!(y > 0.0, "above the camera should be up on screen");
+    }
+
+    #[test]
+    fn project_applies_fov_and_aspect_ratio() {
+        let camera = Camera {
+            fov: 90.0,
+            aspect_ratio: 0.5,
+            ..Default::default()
+        };
+
+        // at 90 degrees the frustum edge is at x == z
+        let point = camera.pos + Vec3::new(2.0, 2.0, 2.0);
+        assert_close(camera.project_vertex(&point), (0.5, 1.0));
+    }
+
+    #[test]
+    fn project_point_behind_camera_off_screen() {
+        let camera = Camera::default();
+        let (x, y) = camera.project_vertex(&(camera.pos - Vec3::new(0.0, 0.0, 1.0)));
+        assert!(x.abs() > 1.0 && y.abs() > 1.0);
+    }
+
+    #[test]
+    fn looking_up_brings_higher_points_to_center() {
+        let mut camera = Camera::default();
+        let point = camera.pos + Vec3::new(0.0, 1.0, 1.0);
+        let (_, before) = camera.project_vertex(&point);
+
+        camera.pitch = 45.0;
+        let (_, after) = camera.project_vertex(&point);
 
-        if tymin > tmin {
-            tmin = tymin;
+        assert!(before > 0.0);
+        assert!(after.abs() < 1e-9);
+    }
+
+    #[test]
+    fn presets_look_at_target() {
+        let target = Vec3::new(1.0, 2.0, 3.0);
+        for preset in ViewPreset::ALL {
+            let mut camera = Camera::default();
+            preset.apply(&mut camera, target, 10.0);
+            assert_close(camera.project_vertex(&target), (0.0, 0.0));
         }
-        if tymax < tmax {
-            tmax = tymax;
+    }
+
+    #[test]
+    fn clip_line_to_near_plane() {
+        let camera = Camera::default();
+        let front = Vec3::new(0.0, 0.0, 2.0);
+        let behind = Vec3::new(0.0, 2.0, -2.0);
+
+        let (a, b) = camera.clip_line_to_near_plane(front, behind).unwrap();
+        assert_eq!(a.z, 2.0);
+        assert!((b.z - camera.near_plane).abs() < 1e-12);
+        assert!((b.y - 0.95).abs() < 1e-12);
+
+        assert!(camera.clip_line_to_near_plane(behind, behind).is_none());
+    }
+
+    #[test]
+    fn ray_aabb_hits_box_in_front() {
+        let camera = Camera::default();
+        let min = Vec3::new(-0.5, -0.5, 2.0);
+        let max = Vec3::new(0.5, 0.5, 3.0);
+
+        assert!(camera.check_ray_aabb_intersections(camera.cast_ray(0.0, 0.0), min, max));
+        assert!(!camera.check_ray_aabb_intersections(camera.cast_ray(0.9, 0.9), min, max));
+    }
+
+    #[test]
+    fn ray_aabb_ignores_box_behind() {
+        let camera = Camera::default();
+        let min = Vec3::new(-0.5, -0.5, -4.0);
+        let max = Vec3::new(0.5, 0.5, -3.0);
+
+        assert!(!camera.check_ray_aabb_intersections(camera.cast_ray(0.0, 0.0), min, max));
+    }
+
+    #[test]
+    fn ray_aabb_from_inside_box() {
+        let camera = Camera::default();
+        let min = camera.pos - Vec3::new(1.0, 1.0, 1.0);
+        let max = camera.pos + Vec3::new(1.0, 1.0, 1.0);
+
+        assert!(camera.check_ray_aabb_intersections(camera.cast_ray(0.3, -0.2), min, max));
+    }
+
+    proptest! {
+        #[test]
+        fn forward_projects_to_center(
+            yaw in -360.0..360.0f64,
+            pitch in -89.0..89.0f64,
+            distance in 0.5..50.0f64,
+        ) {
+            let camera = camera(yaw, pitch);
+            let point = camera.pos + camera.forward() * distance;
+            let (x, y) = camera.project_vertex(&point);
+            prop_assert!(x.abs() < 1e-9 && y.abs() < 1e-9, "({}, {})", x, y);
         }
 
-        let mut tzmin = (min.z - self.pos.z) * inv_dir.z;
-        let mut tzmax = (max.z - self.pos.z) * inv_dir.z;
-        if tzmin > tzmax {
-            std::mem::swap(&mut tzmin, &mut tzmax);
+        #[test]
+        fn cast_ray_inverts_projection(
+            yaw in -360.0..360.0f64,
+            pitch in -89.0..89.0f64,
+            fov in 10.0..150.0f64,
+            aspect_ratio in 0.2..5.0f64,
+            u in -1.0..1.0f64,
+            v in -1.0..1.0f64,
+        ) {
+            let mut camera = camera(yaw, pitch);
+            camera.fov = fov;
+            camera.aspect_ratio = aspect_ratio;
+
+            let ray = camera.cast_ray(u, v);
+            prop_assert!((ray.length() - 1.0).abs() < 1e-9);
+
+            let (x, y) = camera.project_vertex(&(camera.pos + ray));
+            prop_assert!((x - u).abs() < 1e-6 && (y - v).abs() < 1e-6, "({}, {})", x, y);
         }
 
-        if (tmin > tzmax) || (tzmin > tmax) {
-            return false;
+        #[test]
+        fn parallel_rays_invert_projection(
+            yaw in -360.0..360.0f64,
+            pitch in -89.0..89.0f64,
+            depth in 0.0..1.0f64,
+            (u, v) in (-1.0..1.0f64, -1.0..1.0f64),
+            t in 0.5..20.0f64,
+        ) {
+            for projection in [Projection::Orthographic, Projection::Oblique(depth)] {
+                let mut camera = camera(yaw, pitch);
+                camera.projection = projection;
+                camera.extent = 3.0;
+
+                let (origin, ray) = camera.ray(u, v).unwrap();
+                let (x, y) = camera.project_vertex(&(origin + ray * t));
+                prop_assert!((x - u).abs() < 1e-6 && (y - v).abs() < 1e-6, "({}, {})", x, y);
+
+                let point = origin + ray * t;
+                let (origin, ray) = camera.ray_to(point);
+                let end = origin + ray * (point - origin).length();
+                prop_assert!((end - point).length() < 1e-9);
+            }
         }
 
-        true
-    }
+        #[test]
+        fn panoramic_rays_invert_projection(
+            yaw in -360.0..360.0f64,
+            pitch in -89.0..89.0f64,
+            fov in 30.0..360.0f64,
+            (u, v) in (-0.99..0.99f64, -0.99..0.99f64),
+        ) {
+            for projection in [Projection::Fisheye(fov), Projection::Equirectangular] {
+                let mut camera = camera(yaw, pitch);
+                camera.projection = projection;
+                camera.aspect_ratio = 1.0;
+
+                // The point straight behind a fisheye is the whole rim
+                let Some((origin, ray)) = camera.ray(u, v) else {
+                    continue;
+                };
+                if camera.apply_view_transform(ray).z < -0.999 {
+                    continue;
+                }
+                prop_assert!((ray.length() - 1.0).abs() < 1e-9);
+                let (x, y) = camera.project_vertex(&(origin + ray * 2.0));
+                prop_assert!((x - u).abs() < 1e-6 && (y - v).abs() < 1e-6, "({}, {})", x, y);
+            }
+        }
 
-    pub fn camera_to_world(&self, dir: Vec3) -> Vec3 {
-        let forward = self.forward().normalize();
-        let right = self.right().normalize();
-        let up = forward.cross(right).normalize();
+        #[test]
+        fn view_transform_preserves_distance(
+            yaw in -360.0..360.0f64,
+            pitch in -89.0..89.0f64,
+            (x, y, z) in (-10.0..10.0f64, -10.0..10.0f64, -10.0..10.0f64),
+        ) {
+            let camera = camera(yaw, pitch);
+            let point = Vec3::new(x, y, z);
+            let view = camera.apply_view_transform(point);
+            prop_assert!((view.length() - point.length()).abs() < 1e-9);
+        }
 
-        Vec3 {
-            x: dir.x * right.x + dir.y * up.x + dir.z * forward.x,
-            y: dir.x * right.y + dir.y * up.y + dir.z * forward.y,
-            z: dir.x * right.z + dir.y * up.z + dir.z * forward.z,
+        #[test]
+        fn ray_through_box_center_hits(
+            yaw in -360.0..360.0f64,
+            pitch in -89.0..89.0f64,
+            (x, y, z) in (-10.0..10.0f64, -10.0..10.0f64, -10.0..10.0f64),
+            size in 0.01..2.0f64,
+        ) {
+            let camera = camera(yaw, pitch);
+            let center = Vec3::new(x, y, z);
+            let half = Vec3::new(size, size, size);
+            prop_assume!((center - camera.pos).length() > size * 2.0);
+
+            let towards = (center - camera.pos).normalize();
+            let away = towards * -1.0;
+            prop_assert!(camera.check_ray_aabb_intersections(towards, center - half, center + half));
+            prop_assert!(!camera.check_ray_aabb_intersections(away, center - half, center + half));
         }
     }
 }
@@ -0,0 +1,416 @@
+use crate::app::App;
+use crate::console;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use ratatui::style::Color;
+use std::path::PathBuf;
+use termulator::camera::{Projection, ViewPreset};
+use termulator::color::{ColorSupport, Dither};
+use termulator::graphics::{GraphicsBackend, GraphicsProtocol};
+use termulator::headless::{self, Headless};
+use termulator::output::DrawMode;
+use termulator::render::{HiddenLines, Shadows};
+use termulator::scene::Scene;
+use termulator::stereo::StereoMode;
+use termulator::types::Vec3;
+use termulator::viewport::RenderMode;
+
+pub const HELP: &str = "\
+termulator - a 3d renderer in your terminal
+
+Usage: termulator [OPTIONS] [FILE]
+
+Arguments:
+  [FILE]                  OBJ model or scene file to load
+
+Options:
+      --model <FILE>      OBJ model to load
+      --scene <FILE>      Scene file to load
+      --mode <MODE>       Initial render mode (vertex, solid, raycast, raymarch, pathtrace)
+      --lines <LINES>     Edges behind faces in vertex mode (show, hide, dashed)
+      --shadows <SHADOWS> Shadows of the scene lights in raycast modes (off, hard, soft)
+      --marker <MARKER>   Initial marker (braille, dot, halfblock, block, bar, ascii, pixels,
+                          graphics)
+      --pos <X,Y,Z>       Initial camera position
+      --yaw <DEGREES>     Initial camera yaw
+      --pitch <DEGREES>   Initial camera pitch
+      --fov <DEGREES>     Camera field of view
+      --stereo <MODE>     Stereoscopic output (off, anaglyph, side, cross)
+      --projection <PROJECTION>
+                          Camera projection (perspective, orthographic, cavalier, cabinet,
+                          fisheye, equirectangular)
+      --view <VIEW>       Look at the scene from a preset view (isometric, dimetric,
+                          cavalier, cabinet, top, front, side)
+      --bg <COLOR>        Background color (name, index or #rrggbb)
+      --colors <COLORS>   Colors the terminal supports (truecolor, 256, 16, mono),
+                          detected from NO_COLOR, COLORTERM and TERM by default
+      --dither <DITHER>   Dithering for limited colors (none, ordered, floyd)
+      --graphics <PROTOCOL>
+                          Graphics protocol for the graphics marker (sixel, kitty),
+                          detected from TERM and TERM_PROGRAM by default
+      --cell-aspect <RATIO>
+                          Height over width of a terminal cell, measured from the
+                          terminal's pixel size by default
+      --fps <FPS>         Target frames per second (0.1 to 1000)
+  -o, --output <FILE>     Render a single frame to a .ppm, .png or .txt/.ans file and exit
+      --size <WxH>        Output size in pixels for images or cells for text
+      --samples <N>       Paths per pixel of the output in pathtrace mode (default 32)
+  -h, --help              Print this help
+";
+
+pub struct Options {
+    pub scene: Option<PathBuf>,
+    pub render_mode: Option<RenderMode>,
+    pub hidden_lines: Option<HiddenLines>,
+    pub shadows: Option<Shadows>,
+    pub marker: Option<DrawMode>,
+    pub camera_pos: Option<Vec3>,
+    pub yaw: Option<f64>,
+    pub pitch: Option<f64>,
+    pub fov: Option<f64>,
+    pub projection: Option<Projection>,
+    pub stereo: Option<StereoMode>,
+    pub view: Option<ViewPreset>,
+    pub background: Option<Color>,
+    pub color_support: Option<ColorSupport>,
+    pub dither: Option<Dither>,
+    pub graphics: Option<GraphicsProtocol>,
+    pub cell_aspect: Option<f64>,
+    pub target_fps: Option<f64>,
+    pub headless: Option<Headless>,
+    size: Option<(u32, u32)>,
+    samples: Option<usize>,
+}
+impl Options {
+    pub fn default() -> Self {
+        Self {
+            scene: None,
+            render_mode: None,
+            hidden_lines: None,
+            shadows: None,
+            marker: None,
+            camera_pos: None,
+            yaw: None,
+            pitch: None,
+            fov: None,
+            projection: None,
+            stereo: None,
+            view: None,
+            background: None,
+            color_support: None,
+            dither: None,
+            graphics: None,
+            cell_aspect: None,
+            target_fps: None,
+            headless: None,
+            size: None,
+            samples: None,
+        }
+    }
+
+    // Returns None when --help was requested
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+
+            if flag == "-h" || flag == "--help" {
+                return Ok(None);
+            }
+            if !flag.starts_with('-') {
+                options.set_scene(PathBuf::from(flag))?;
+                continue;
+            }
+
+            let value = match inline_value.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(eyre!("{} requires a value", flag)),
+            };
+            let context = || format!("invalid value for {}", flag);
+
+            match flag.as_str() {
+                "--model" | "--scene" => options.set_scene(PathBuf::from(value))?,
+                "--mode" => {
+                    options.render_mode =
+                        Some(console::parse_render_mode(&value).map_err(|e| eyre!(e))?)
+                }
+                "--lines" => {
+                    options.hidden_lines =
+                        Some(console::parse_hidden_lines(&value).map_err(|e| eyre!(e))?)
+                }
+                "--shadows" => {
+                    options.shadows = Some(console::parse_shadows(&value).map_err(|e| eyre!(e))?)
+                }
+                "--marker" => {
+                    options.marker = Some(console::parse_marker(&value).map_err(|e| eyre!(e))?)
+                }
+                "--pos" => options.camera_pos = Some(parse_vec3(&value).wrap_err_with(context)?),
+                "--yaw" => options.yaw = Some(parse_finite(&value).wrap_err_with(context)?),
+                "--pitch" => options.pitch = Some(parse_finite(&value).wrap_err_with(context)?),
+                "--fov" => {
+                    let fov = parse_finite(&value).wrap_err_with(context)?;
+                    if !(1.0..=179.0).contains(&fov) {
+                        return Err(eyre!("--fov must be between 1 and 179"));
+                    }
+                    options.fov = Some(fov);
+                }
+                "--projection" => {
+                    options.projection =
+                        Some(console::parse_projection(&value).map_err(|e| eyre!(e))?)
+                }
+                "--stereo" => {
+                    options.stereo = Some(console::parse_stereo(&value).map_err(|e| eyre!(e))?)
+                }
+                "--view" => options.view = Some(console::parse_view(&value).map_err(|e| eyre!(e))?),
+                "--bg" => options.background = Some(value.parse().wrap_err_with(context)?),
+                "--colors" => {
+                    options.color_support =
+                        Some(console::parse_color_support(&value).map_err(|e| eyre!(e))?)
+                }
+                "--dither" => {
+                    options.dither = Some(console::parse_dither(&value).map_err(|e| eyre!(e))?)
+                }
+                "--graphics" => {
+                    options.graphics = Some(console::parse_graphics(&value).map_err(|e| eyre!(e))?)
+                }
+                "--cell-aspect" => {
+                    let cell_aspect = parse_finite(&value).wrap_err_with(context)?;
+                    if cell_aspect <= 0.0 {
+                        return Err(eyre!("--cell-aspect must be positive"));
+                    }
+                    options.cell_aspect = Some(cell_aspect);
+                }
+                "--fps" => {
+                    let fps = parse_finite(&value).wrap_err_with(context)?;
+                    // The frame time has to fit a Duration
+                    if !(MIN_FPS..=MAX_FPS).contains(&fps) {
+                        return Err(eyre!("--fps must be between {} and {}", MIN_FPS, MAX_FPS));
+                    }
+                    options.target_fps = Some(fps);
+                }
+                "-o" | "--output" => {
+                    options.headless = Some(Headless {
+                        output: PathBuf::from(value),
+                        size: None,
+                        samples: headless::DEFAULT_SAMPLES,
+                    })
+                }
+                "--size" => options.size = Some(parse_size(&value).wrap_err_with(context)?),
+                "--samples" => {
+                    let samples: usize = value.parse().wrap_err_with(context)?;
+                    if samples == 0 {
+                        return Err(eyre!("--samples must be positive"));
+                    }
+                    options.samples = Some(samples);
+                }
+                _ => return Err(eyre!("unknown option {}, see --help", flag)),
+            }
+        }
+
+        match &mut options.headless {
+            Some(headless) => {
+                headless.size = options.size;
+                headless.samples = options.samples.unwrap_or(headless.samples);
+            }
+            None if options.size.is_some() => return Err(eyre!("--size requires --output")),
+            None if options.samples.is_some() => return Err(eyre!("--samples requires --output")),
+            None => (),
+        }
+
+        Ok(Some(options))
+    }
+
+    pub fn apply(self, app: &mut App) -> Result<()> {
+        if let Some(path) = self.scene {
+            app.scene = Scene::load(path)?;
+        }
+        if let Some(mode) = self.render_mode {
+            app.render_mode = mode;
+        }
+        if let Some(hidden_lines) = self.hidden_lines {
+            app.hidden_lines = hidden_lines;
+        }
+        if let Some(shadows) = self.shadows {
+            app.shadows = shadows;
+        }
+        if let Some(marker) = self.marker {
+            app.draw_mode = marker;
+        }
+        if let Some(pos) = self.camera_pos {
+            app.viewport.camera.pos = pos;
+        }
+        if let Some(yaw) = self.yaw {
+            app.viewport.camera.yaw = yaw;
+        }
+        if let Some(pitch) = self.pitch {
+            app.viewport.camera.pitch = pitch.clamp(-89.0, 89.0);
+        }
+        if let Some(fov) = self.fov {
+            app.viewport.camera.fov = fov;
+        }
+        // A view places the camera itself, a projection given with it wins
+        if let Some(view) = self.view {
+            app.apply_view(view);
+        }
+        if let Some(projection) = self.projection {
+            app.set_projection(projection);
+        }
+        if let Some(mode) = self.stereo {
+            app.stereo.mode = mode;
+        }
+        if let Some(background) = self.background {
+            app.background = background;
+        }
+        // Files are not bound to what the current terminal supports
+        match self.color_support {
+            Some(color_support) => app.color_support = color_support,
+            None if self.headless.is_some() => app.color_support = ColorSupport::TrueColor,
+            None => (),
+        }
+        if let Some(dither) = self.dither {
+            app.dither = dither;
+        }
+        if let Some(protocol) = self.graphics {
+            // Guess a common cell size when the terminal doesn't report it
+            let cell_size = app.cell_size.unwrap_or((10, 20));
+            app.graphics = Some(GraphicsBackend::with_cell_size(protocol, cell_size));
+        }
+        if let Some(cell_aspect) = self.cell_aspect {
+            app.cell_aspect = cell_aspect;
+        }
+        if let Some(fps) = self.target_fps {
+            app.frame_time = std::time::Duration::from_secs_f64(1.0 / fps);
+        }
+        Ok(())
+    }
+
+    fn set_scene(&mut self, path: PathBuf) -> Result<()> {
+        if self.scene.is_some() {
+            return Err(eyre!("only one model or scene file can be given"));
+        }
+        self.scene = Some(path);
+        Ok(())
+    }
+}
+
+const MIN_FPS: f64 = 0.1;
+const MAX_FPS: f64 = 1000.0;
+
+// Rejects nan and infinities, which parse as floats but break the camera
+fn parse_finite(value: &str) -> Result<f64> {
+    let number: f64 = value.trim().parse()?;
+    if !number.is_finite() {
+        return Err(eyre!("{} is not a finite number", value));
+    }
+    Ok(number)
+}
+
+fn parse_vec3(value: &str) -> Result<Vec3> {
+    let coords = value
+        .split(',')
+        .map(parse_finite)
+        .collect::<Result<Vec<_>>>()?;
+
+    match *coords.as_slice() {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(eyre!("expected X,Y,Z")),
+    }
+}
+
+// Larger sizes fail to allocate their framebuffer instead of erroring
+const MAX_SIDE: u32 = 16384;
+const MAX_PIXELS: u64 = 1 << 25;
+
+pub fn parse_size(value: &str) -> Result<(u32, u32)> {
+    let (width, height) = value.split_once(['x', 'X']).ok_or(eyre!("expected WxH"))?;
+    let size: (u32, u32) = (width.trim().parse()?, height.trim().parse()?);
+    if size.0 == 0 || size.1 == 0 {
+        return Err(eyre!("size must not be zero"));
+    }
+    if size.0 > MAX_SIDE || size.1 > MAX_SIDE || size.0 as u64 * size.1 as u64 > MAX_PIXELS {
+        return Err(eyre!(
+            "size must be at most {}x{} and {} pixels",
+            MAX_SIDE,
+            MAX_SIDE,
+            MAX_PIXELS
+        ));
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_flags_and_values() {
+        let options = parse(&[
+            "scene.scene",
+            "--mode",
+            "raycast",
+            "--pos=1,2,3",
+            "--yaw",
+            "-30",
+            "--fps",
+            "30",
+            "-o",
+            "out.png",
+            "--size",
+            "320x200",
+            "--samples",
+            "64",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.scene, Some(PathBuf::from("scene.scene")));
+        assert_eq!(options.render_mode, Some(RenderMode::Raycast));
+        let pos = options.camera_pos.unwrap();
+        assert_eq!((pos.x, pos.y, pos.z), (1.0, 2.0, 3.0));
+        assert_eq!((options.yaw, options.target_fps), (Some(-30.0), Some(30.0)));
+        let headless = options.headless.unwrap();
+        assert_eq!(headless.output, PathBuf::from("out.png"));
+        assert_eq!(headless.size, Some((320, 200)));
+        assert_eq!(headless.samples, 64);
+
+        assert!(parse(&["--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for args in [
+            &["--fps", "nan"][..],
+            &["--fps", "0"],
+            &["--fps", "1e-320"],
+            &["--fps", "inf"],
+            &["--yaw", "nan"],
+            &["--pitch", "-inf"],
+            &["--cell-aspect", "nan"],
+            &["--cell-aspect", "0"],
+            &["--pos", "0,nan,0"],
+            &["--fov", "180"],
+            &["--size", "0x10"],
+            &["--size", "10x10"],
+            &["-o", "out.png", "--size", "100000x100000"],
+            &["-o", "out.png", "--size", "16384x16384"],
+            &["-o", "out.png", "--samples", "0"],
+            &["-o", "out.png", "--samples", "2.5"],
+            &["--samples", "4"],
+            &["--mode"],
+            &["--unknown", "1"],
+            &["a.obj", "b.obj"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use crate::framebuffer::Framebuffer;
+
+use ratatui::style::Color;
+
+// xterm's default values for the 16 standard colors
+pub const ANSI_16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// Resolves a terminal color to RGB, `Color::Reset` resolves to the given default
+pub fn to_rgb(color: Color, default: [u8; 3]) -> [u8; 3] {
+    match color {
+        Color::Reset => default,
+        Color::Black => ANSI_16[0],
+        Color::Red => ANSI_16[1],
+        Color::Green => ANSI_16[2],
+        Color::Yellow => ANSI_16[3],
+        Color::Blue => ANSI_16[4],
+        Color::Magenta => ANSI_16[5],
+        Color::Cyan => ANSI_16[6],
+        Color::Gray => ANSI_16[7],
+        Color::DarkGray => ANSI_16[8],
+        Color::LightRed => ANSI_16[9],
+        Color::LightGreen => ANSI_16[10],
+        Color::LightYellow => ANSI_16[11],
+        Color::LightBlue => ANSI_16[12],
+        Color::LightMagenta => ANSI_16[13],
+        Color::LightCyan => ANSI_16[14],
+        Color::White => ANSI_16[15],
+        Color::Rgb(r, g, b) => [r, g, b],
+        Color::Indexed(index) => indexed_to_rgb(index),
+    }
+}
+
+pub fn indexed_to_rgb(index: u8) -> [u8; 3] {
+    match index {
+        0..=15 => ANSI_16[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            [
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[(i / 6 % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            ]
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            [level, level, level]
+        }
+    }
+}
+
+// How many colors the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    Monochrome,
+}
+impl ColorSupport {
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        Self::from_env(&var("NO_COLOR"), &var("COLORTERM"), &var("TERM"))
+    }
+
+    // https://no-color.org asks for no color whenever NO_COLOR is set and not empty
+    pub fn from_env(no_color: &str, colorterm: &str, term: &str) -> Self {
+        if !no_color.is_empty() || term == "dumb" {
+            ColorSupport::Monochrome
+        } else if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+
+    // The closest color the terminal can show, with its RGB value
+    pub fn nearest(self, rgb: [u8; 3]) -> (Color, [u8; 3]) {
+        match self {
+            ColorSupport::TrueColor => (Color::Rgb(rgb[0], rgb[1], rgb[2]), rgb),
+            // The first 16 colors vary between terminals, so only the color
+            // cube and the grayscale ramp are used
+            ColorSupport::Ansi256 => {
+                let index = (16..=255)
+                    .min_by_key(|&index| distance(rgb, indexed_to_rgb(index)))
+                    .unwrap();
+                (Color::Indexed(index), indexed_to_rgb(index))
+            }
+            ColorSupport::Ansi16 => {
+                let index = (0..16)
+                    .min_by_key(|&index| distance(rgb, ANSI_16[index]))
+                    .unwrap();
+                (ANSI_16_COLORS[index], ANSI_16[index])
+            }
+            ColorSupport::Monochrome => (Color::Reset, rgb),
+        }
+    }
+
+    // Converts a color from the buffer to one the terminal can show
+    pub fn convert(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorSupport::Monochrome, _) => Color::Reset,
+            (_, Color::Rgb(r, g, b)) => self.nearest([r, g, b]).0,
+            (ColorSupport::Ansi16, Color::Indexed(index)) => self.nearest(indexed_to_rgb(index)).0,
+            _ => color,
+        }
+    }
+
+    // How far apart neighbouring palette colors are, which is how much ordered
+    // dithering has to move a color to reach them
+    fn spread(self) -> f32 {
+        match self {
+            ColorSupport::TrueColor => 0.0,
+            ColorSupport::Ansi256 => 40.0,
+            ColorSupport::Ansi16 => 128.0,
+            ColorSupport::Monochrome => 255.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dither {
+    None,
+    Ordered,
+    FloydSteinberg,
+}
+
+const ANSI_16_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+// The brightest channel rather than luminance, so a fully lit red face still
+// counts as bright
+pub fn brightness(color: [u8; 3]) -> u8 {
+    color.into_iter().max().unwrap_or(0)
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(a[0], b[0]) + d(a[1], b[1]) + d(a[2], b[2])
+}
+
+// Snaps every drawn pixel to the nearest color the terminal can show. Without
+// colors, pixels are instead kept or dropped by their brightness.
+pub fn quantize(framebuffer: &mut Framebuffer, support: ColorSupport, dither: Dither) {
+    if support == ColorSupport::TrueColor {
+        return;
+    }
+    const BAYER: [[f32; 4]; 4] = [
+        [0.0, 8.0, 2.0, 10.0],
+        [12.0, 4.0, 14.0, 6.0],
+        [3.0, 11.0, 1.0, 9.0],
+        [15.0, 7.0, 13.0, 5.0],
+    ];
+
+    // Error carried over to the pixels right and below by Floyd–Steinberg
+    let mut error = vec![[0.0f32; 3]; framebuffer.width * framebuffer.height];
+
+    for y in 0..framebuffer.height {
+        for x in 0..framebuffer.width {
+            let i = framebuffer.index(x, y);
+            if framebuffer.coverage[i] <= 0.0 {
+                continue;
+            }
+
+            let mut wanted = framebuffer.color[i].map(|c| c as f32);
+            if support == ColorSupport::Monochrome {
+                wanted = [brightness(framebuffer.color[i]) as f32; 3];
+            }
+            match dither {
+                Dither::None => (),
+                Dither::Ordered => {
+                    let offset = (BAYER[y % 4][x % 4] + 0.5) / 16.0 - 0.5;
+                    wanted = wanted.map(|c| c + offset * support.spread());
+                }
+                Dither::FloydSteinberg => {
+                    for (c, e) in wanted.iter_mut().zip(error[i]) {
+                        *c += e;
+                    }
+                }
+            }
+
+            let shown = if support == ColorSupport::Monochrome {
+                if wanted[0] > 127.5 {
+                    framebuffer.color[i] = [255; 3];
+                } else {
+                    framebuffer.color[i] = framebuffer.background;
+                    framebuffer.coverage[i] = 0.0;
+                }
+                [255 * (wanted[0] > 127.5) as u8; 3]
+            } else {
+                let clamped = wanted.map(|c| c.clamp(0.0, 255.0).round() as u8);
+                let (_, rgb) = support.nearest(clamped);
+                framebuffer.color[i] = rgb;
+                rgb
+            };
+
+            if dither == Dither::FloydSteinberg {
+                let diff: [f32; 3] = std::array::from_fn(|c| wanted[c] - shown[c] as f32);
+                let mut spread = |dx: isize, dy: usize, weight: f32| {
+                    let (nx, ny) = (x as isize + dx, y + dy);
+                    if nx < 0 || nx as usize >= framebuffer.width || ny >= framebuffer.height {
+                        return;
+                    }
+                    let n = ny * framebuffer.width + nx as usize;
+                    for c in 0..3 {
+                        error[n][c] += diff[c] * weight;
+                    }
+                };
+                spread(1, 0, 7.0 / 16.0);
+                spread(-1, 1, 3.0 / 16.0);
+                spread(0, 1, 5.0 / 16.0);
+                spread(1, 1, 1.0 / 16.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_color_support_from_env() {
+        let detect = ColorSupport::from_env;
+        assert_eq!(
+            detect("1", "truecolor", "xterm-256color"),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(detect("", "truecolor", "xterm"), ColorSupport::TrueColor);
+        assert_eq!(detect("", "", "xterm-256color"), ColorSupport::Ansi256);
+        assert_eq!(detect("", "", "xterm"), ColorSupport::Ansi16);
+        assert_eq!(detect("", "", "dumb"), ColorSupport::Monochrome);
+    }
+
+    #[test]
+    fn nearest_picks_palette_colors() {
+        assert_eq!(
+            ColorSupport::Ansi256.nearest([200, 10, 10]),
+            (Color::Indexed(160), [215, 0, 0])
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.nearest([100, 100, 100]).1,
+            [98, 98, 98]
+        );
+        assert_eq!(ColorSupport::Ansi16.nearest([200, 10, 10]).0, Color::Red);
+        assert_eq!(
+            ColorSupport::Ansi16.convert(Color::Indexed(196)),
+            Color::LightRed
+        );
+    }
+
+    fn gradient() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(16, 16, [0, 0, 0]);
+        for y in 0..16 {
+            for x in 0..16 {
+                let value = (x * 16 + 8) as u8;
+                framebuffer.plot(x, y, 1.0, [value, 0, 0], 1.0);
+            }
+        }
+        framebuffer
+    }
+
+    #[test]
+    fn quantize_keeps_average_brightness() {
+        for dither in [Dither::Ordered, Dither::FloydSteinberg] {
+            let mut framebuffer = gradient();
+            quantize(&mut framebuffer, ColorSupport::Monochrome, dither);
+
+            // Every group of columns keeps about as many pixels as it is bright
+            for group in 0..4 {
+                let columns = group * 4..group * 4 + 4;
+                let on = columns
+                    .clone()
+                    .flat_map(|x| (0..16).map(move |y| (x, y)))
+                    .filter(|&(x, y)| framebuffer.coverage[framebuffer.index(x, y)] > 0.0)
+                    .count();
+                let expected: f64 = columns.map(|x| (x * 16 + 8) as f64 / 255.0 * 16.0).sum();
+                assert!(
+                    (on as f64 - expected).abs() <= 4.0,
+                    "{:?} columns {}: {} instead of {}",
+                    dither,
+                    group,
+                    on,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quantize_without_dither_snaps_to_palette() {
+        let mut framebuffer = gradient();
+        quantize(&mut framebuffer, ColorSupport::Ansi16, Dither::None);
+        assert!(framebuffer
+            .color
+            .iter()
+            .all(|color| ANSI_16.contains(color)));
+    }
+}
@@ -0,0 +1,627 @@
+use crate::app::Action;
+use crate::cli;
+use std::path::PathBuf;
+use termulator::camera::{Projection, ViewPreset};
+use termulator::color::{ColorSupport, Dither};
+use termulator::graphics::GraphicsProtocol;
+use termulator::headless::{self, Headless};
+use termulator::output::DrawMode;
+use termulator::render::{HiddenLines, Shadows, SOFT_SHADOW_SAMPLES};
+use termulator::stereo::StereoMode;
+use termulator::types::Vec3;
+use termulator::viewport::RenderMode;
+
+const COMMANDS: [&str; 21] = [
+    "fov",
+    "tp",
+    "speed",
+    "projection",
+    "view",
+    "zoom",
+    "layout",
+    "stereo",
+    "eyes",
+    "convergence",
+    "load",
+    "save",
+    "export",
+    "render",
+    "mode",
+    "lines",
+    "shadows",
+    "marker",
+    "colors",
+    "dither",
+    "quit",
+];
+const RENDER_MODES: [&str; 5] = ["vertex", "solid", "raycast", "raymarch", "pathtrace"];
+const HIDDEN_LINES: [&str; 3] = ["show", "hide", "dashed"];
+const SHADOWS: [&str; 3] = ["off", "hard", "soft"];
+const STEREO_MODES: [&str; 4] = ["off", "anaglyph", "side", "cross"];
+const LAYOUTS: [&str; 2] = ["single", "quad"];
+const PROJECTIONS: [&str; 6] = [
+    "perspective",
+    "orthographic",
+    "cavalier",
+    "cabinet",
+    "fisheye",
+    "equirectangular",
+];
+const VIEWS: [&str; 7] = [
+    "isometric",
+    "dimetric",
+    "cavalier",
+    "cabinet",
+    "top",
+    "front",
+    "side",
+];
+const MARKERS: [&str; 8] = [
+    "braille",
+    "dot",
+    "halfblock",
+    "block",
+    "bar",
+    "ascii",
+    "pixels",
+    "graphics",
+];
+const COLOR_SUPPORTS: [&str; 4] = ["truecolor", "256", "16", "mono"];
+const DITHERS: [&str; 3] = ["none", "ordered", "floyd"];
+
+pub struct Console {
+    pub active: bool,
+    pub input: String,
+    pub message: Option<ConsoleMessage>,
+    history: Vec<String>,
+    history_index: Option<usize>,
+}
+
+pub enum ConsoleMessage {
+    Info(String),
+    Error(String),
+}
+
+pub enum ConsoleInput {
+    Char(char),
+    Backspace,
+    Complete,
+    HistoryPrevious,
+    HistoryNext,
+    Submit,
+    Close,
+}
+
+impl Console {
+    pub fn default() -> Self {
+        Self {
+            active: false,
+            input: String::new(),
+            message: None,
+            history: Vec::new(),
+            history_index: None,
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.active = true;
+        self.input.clear();
+        self.message = None;
+        self.history_index = None;
+    }
+
+    // Returns the submitted command line when the input is submitted
+    pub fn handle_input(&mut self, input: ConsoleInput) -> Option<String> {
+        match input {
+            ConsoleInput::Char(c) => self.input.push(c),
+            ConsoleInput::Backspace => {
+                if self.input.pop().is_none() {
+                    self.active = false;
+                }
+            }
+            ConsoleInput::Complete => self.complete(),
+            ConsoleInput::HistoryPrevious => {
+                let index = match self.history_index {
+                    Some(index) => index.saturating_sub(1),
+                    None => self.history.len().checked_sub(1)?,
+                };
+                self.history_index = Some(index);
+                self.input = self.history[index].clone();
+            }
+            ConsoleInput::HistoryNext => {
+                let index = self.history_index? + 1;
+                if index < self.history.len() {
+                    self.history_index = Some(index);
+                    self.input = self.history[index].clone();
+                } else {
+                    self.history_index = None;
+                    self.input.clear();
+                }
+            }
+            ConsoleInput::Submit => {
+                self.active = false;
+                self.history_index = None;
+                let line = std::mem::take(&mut self.input).trim().to_string();
+                if line.is_empty() {
+                    return None;
+                }
+                if self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                }
+                return Some(line);
+            }
+            ConsoleInput::Close => {
+                self.active = false;
+                self.input.clear();
+            }
+        }
+        None
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.message = Some(ConsoleMessage::Info(message.into()));
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.message = Some(ConsoleMessage::Error(message.into()));
+    }
+
+    fn complete(&mut self) {
+        let (head, word) = match self.input.rfind(' ') {
+            Some(split) => self.input.split_at(split + 1),
+            None => ("", self.input.as_str()),
+        };
+
+        let candidates: Vec<String> = match head.split_whitespace().next() {
+            None => to_strings(&COMMANDS),
+            Some("mode") => to_strings(&RENDER_MODES),
+            Some("lines") => to_strings(&HIDDEN_LINES),
+            Some("shadows") => to_strings(&SHADOWS),
+            Some("projection") => to_strings(&PROJECTIONS),
+            Some("view") => to_strings(&VIEWS),
+            Some("layout") => to_strings(&LAYOUTS),
+            Some("stereo") => to_strings(&STEREO_MODES),
+            Some("marker") => to_strings(&MARKERS),
+            Some("colors") => to_strings(&COLOR_SUPPORTS),
+            Some("dither") => to_strings(&DITHERS),
+            Some("load" | "save" | "export" | "render") => complete_path(word),
+            Some(_) => Vec::new(),
+        };
+
+        let matches: Vec<&String> = candidates.iter().filter(|c| c.starts_with(word)).collect();
+        let Some(first) = matches.first() else {
+            return;
+        };
+
+        // Extend to the longest prefix shared by all candidates
+        let mut common = first.as_str();
+        for candidate in &matches[1..] {
+            let shared = common
+                .char_indices()
+                .zip(candidate.chars())
+                .take_while(|((_, a), b)| a == b)
+                .last()
+                .map_or(0, |((i, a), _)| i + a.len_utf8());
+            common = &common[..shared];
+        }
+
+        let mut completed = format!("{}{}", head, common);
+        if matches.len() == 1 && !common.ends_with('/') {
+            completed.push(' ');
+        }
+        self.input = completed;
+    }
+}
+
+fn to_strings(words: &[&str]) -> Vec<String> {
+    words.iter().map(|w| w.to_string()).collect()
+}
+
+fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(split) => word.split_at(split + 1),
+        None => ("", word),
+    };
+
+    let read_dir = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = std::fs::read_dir(read_dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) || name.starts_with('.') && !prefix.starts_with('.') {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect()
+}
+
+pub fn parse_command(line: &str) -> Result<Action, String> {
+    let mut parts = line.split_whitespace();
+    let command = parts.next().unwrap_or_default();
+    let args: Vec<&str> = parts.collect();
+
+    match command {
+        "fov" => {
+            let [fov] = parse_numbers::<1>(&args)?;
+            if !(1.0..=179.0).contains(&fov) {
+                return Err(format!("fov must be between 1 and 179, got {}", fov));
+            }
+            Ok(Action::SetFov(fov))
+        }
+        "tp" => {
+            let [x, y, z] = parse_numbers::<3>(&args)?;
+            Ok(Action::Teleport(Vec3::new(x, y, z)))
+        }
+        "speed" => {
+            let [speed] = parse_numbers::<1>(&args)?;
+            if speed <= 0.0 {
+                return Err(String::from("speed must be positive"));
+            }
+            Ok(Action::SetMoveSpeed(speed))
+        }
+        "projection" => match args.as_slice() {
+            ["fisheye", _] => {
+                let [fov] = parse_numbers::<1>(&args[1..])?;
+                if !(1.0..=360.0).contains(&fov) {
+                    return Err(format!(
+                        "fisheye fov must be between 1 and 360, got {}",
+                        fov
+                    ));
+                }
+                Ok(Action::ChangeProjection(Projection::Fisheye(fov)))
+            }
+            [projection] => parse_projection(projection).map(Action::ChangeProjection),
+            _ => Err(format!(
+                "usage: projection <{}>, or projection fisheye <fov>",
+                PROJECTIONS.join("|")
+            )),
+        },
+        "view" => match args.as_slice() {
+            [view] => parse_view(view).map(Action::ChangeView),
+            _ => Err(format!("usage: view <{}>", VIEWS.join("|"))),
+        },
+        "zoom" => {
+            let [extent] = parse_numbers::<1>(&args)?;
+            if extent <= 0.0 {
+                return Err(String::from("zoom extent must be positive"));
+            }
+            Ok(Action::SetExtent(extent))
+        }
+        "stereo" => match args.as_slice() {
+            [mode] => parse_stereo(mode).map(Action::ChangeStereo),
+            _ => Err(format!("usage: stereo <{}>", STEREO_MODES.join("|"))),
+        },
+        "eyes" => {
+            let [distance] = parse_numbers::<1>(&args)?;
+            if distance < 0.0 {
+                return Err(String::from("eye distance can't be negative"));
+            }
+            Ok(Action::SetEyeDistance(distance))
+        }
+        "convergence" => {
+            let [distance] = parse_numbers::<1>(&args)?;
+            if distance <= 0.0 {
+                return Err(String::from("convergence distance must be positive"));
+            }
+            Ok(Action::SetConvergence(distance))
+        }
+        "layout" => match args.as_slice() {
+            ["single"] => Ok(Action::SetQuad(false)),
+            ["quad"] => Ok(Action::SetQuad(true)),
+            _ => Err(format!("usage: layout <{}>", LAYOUTS.join("|"))),
+        },
+        "load" => match args.as_slice() {
+            [] => Err(String::from("usage: load <file.obj|file.scene>")),
+            _ => Ok(Action::Load(PathBuf::from(args.join(" ")))),
+        },
+        "save" => match args.as_slice() {
+            [] => Err(String::from("usage: save <file.scene>")),
+            _ => Ok(Action::Save(PathBuf::from(args.join(" ")))),
+        },
+        "export" => match args.as_slice() {
+            [] => Err(String::from("usage: export <file.obj>")),
+            _ => Ok(Action::Export(PathBuf::from(args.join(" ")))),
+        },
+        "render" => match args.as_slice() {
+            [] => Err(String::from(
+                "usage: render <file.png|ppm|txt|ans> [WxH] [samples]",
+            )),
+            [output, options @ ..] => {
+                let mut headless = Headless {
+                    output: PathBuf::from(output),
+                    size: None,
+                    samples: headless::DEFAULT_SAMPLES,
+                };
+                for option in options {
+                    if let Ok(size) = cli::parse_size(option) {
+                        headless.size = Some(size);
+                    } else {
+                        headless.samples = option
+                            .parse()
+                            .ok()
+                            .filter(|&samples| samples > 0)
+                            .ok_or_else(|| format!("expected WxH or samples, got {}", option))?;
+                    }
+                }
+                Ok(Action::Render(headless))
+            }
+        },
+        "mode" => match args.as_slice() {
+            [mode] => parse_render_mode(mode).map(Action::ChangeRenderMode),
+            _ => Err(format!("usage: mode <{}>", RENDER_MODES.join("|"))),
+        },
+        "lines" => match args.as_slice() {
+            [lines] => parse_hidden_lines(lines).map(Action::ChangeHiddenLines),
+            _ => Err(format!("usage: lines <{}>", HIDDEN_LINES.join("|"))),
+        },
+        "shadows" => match args.as_slice() {
+            ["soft", samples] => samples
+                .parse()
+                .ok()
+                .filter(|samples| (1..=256).contains(samples))
+                .map(|samples| Action::ChangeShadows(Shadows::Soft(samples)))
+                .ok_or_else(|| {
+                    format!(
+                        "soft shadow samples must be a whole number from 1 to 256, got {}",
+                        samples
+                    )
+                }),
+            [shadows] => parse_shadows(shadows).map(Action::ChangeShadows),
+            _ => Err(format!(
+                "usage: shadows <{}>, or shadows soft <samples>",
+                SHADOWS.join("|")
+            )),
+        },
+        "marker" => match args.as_slice() {
+            [marker] => parse_marker(marker).map(Action::ChangeDrawMode),
+            _ => Err(format!("usage: marker <{}>", MARKERS.join("|"))),
+        },
+        "colors" => match args.as_slice() {
+            [colors] => parse_color_support(colors).map(Action::ChangeColorSupport),
+            _ => Err(format!("usage: colors <{}>", COLOR_SUPPORTS.join("|"))),
+        },
+        "dither" => match args.as_slice() {
+            [dither] => parse_dither(dither).map(Action::ChangeDither),
+            _ => Err(format!("usage: dither <{}>", DITHERS.join("|"))),
+        },
+        "quit" | "q" => Ok(Action::Quit),
+        _ => Err(format!("unknown command: {}", command)),
+    }
+}
+
+pub fn parse_render_mode(mode: &str) -> Result<RenderMode, String> {
+    match mode {
+        "vertex" => Ok(RenderMode::Vertex),
+        "solid" => Ok(RenderMode::Solid),
+        "raycast" => Ok(RenderMode::Raycast),
+        "raymarch" => Ok(RenderMode::Raymarch),
+        "pathtrace" => Ok(RenderMode::PathTrace),
+        _ => Err(format!("unknown render mode: {}", mode)),
+    }
+}
+
+pub fn parse_marker(marker: &str) -> Result<DrawMode, String> {
+    match marker {
+        "braille" => Ok(DrawMode::Braille),
+        "dot" => Ok(DrawMode::Dot),
+        "halfblock" => Ok(DrawMode::HalfBlock),
+        "block" => Ok(DrawMode::Block),
+        "bar" => Ok(DrawMode::Bar),
+        "ascii" => Ok(DrawMode::Ascii),
+        "pixels" => Ok(DrawMode::Pixels),
+        "graphics" => Ok(DrawMode::Graphics),
+        _ => Err(format!("unknown marker: {}", marker)),
+    }
+}
+
+pub fn parse_color_support(colors: &str) -> Result<ColorSupport, String> {
+    match colors {
+        "truecolor" | "24bit" => Ok(ColorSupport::TrueColor),
+        "256" => Ok(ColorSupport::Ansi256),
+        "16" => Ok(ColorSupport::Ansi16),
+        "mono" | "none" => Ok(ColorSupport::Monochrome),
+        _ => Err(format!("unknown color support: {}", colors)),
+    }
+}
+
+pub fn parse_projection(projection: &str) -> Result<Projection, String> {
+    match projection {
+        "perspective" => Ok(Projection::Perspective),
+        "orthographic" | "ortho" => Ok(Projection::Orthographic),
+        "cavalier" => Ok(Projection::Oblique(1.0)),
+        "cabinet" => Ok(Projection::Oblique(0.5)),
+        "fisheye" => Ok(Projection::Fisheye(180.0)),
+        "equirectangular" | "panorama" => Ok(Projection::Equirectangular),
+        _ => Err(format!("unknown projection: {}", projection)),
+    }
+}
+
+pub fn parse_view(view: &str) -> Result<ViewPreset, String> {
+    match view {
+        "isometric" | "iso" => Ok(ViewPreset::Isometric),
+        "dimetric" => Ok(ViewPreset::Dimetric),
+        "cavalier" => Ok(ViewPreset::Cavalier),
+        "cabinet" => Ok(ViewPreset::Cabinet),
+        "top" => Ok(ViewPreset::Top),
+        "front" => Ok(ViewPreset::Front),
+        "side" => Ok(ViewPreset::Side),
+        _ => Err(format!("unknown view: {}", view)),
+    }
+}
+
+pub fn parse_stereo(mode: &str) -> Result<StereoMode, String> {
+    match mode {
+        "off" => Ok(StereoMode::Off),
+        "anaglyph" => Ok(StereoMode::Anaglyph),
+        "side" | "side-by-side" => Ok(StereoMode::SideBySide),
+        "cross" | "cross-eyed" => Ok(StereoMode::CrossEyed),
+        _ => Err(format!("unknown stereo mode: {}", mode)),
+    }
+}
+
+pub fn parse_hidden_lines(lines: &str) -> Result<HiddenLines, String> {
+    match lines {
+        "show" => Ok(HiddenLines::Show),
+        "hide" => Ok(HiddenLines::Hide),
+        "dashed" => Ok(HiddenLines::Dashed),
+        _ => Err(format!("unknown hidden lines: {}", lines)),
+    }
+}
+
+pub fn parse_shadows(shadows: &str) -> Result<Shadows, String> {
+    match shadows {
+        "off" => Ok(Shadows::Off),
+        "hard" => Ok(Shadows::Hard),
+        "soft" => Ok(Shadows::Soft(SOFT_SHADOW_SAMPLES)),
+        _ => Err(format!("unknown shadows: {}", shadows)),
+    }
+}
+
+pub fn parse_dither(dither: &str) -> Result<Dither, String> {
+    match dither {
+        "none" => Ok(Dither::None),
+        "ordered" | "bayer" => Ok(Dither::Ordered),
+        "floyd" | "floyd-steinberg" => Ok(Dither::FloydSteinberg),
+        _ => Err(format!("unknown dither: {}", dither)),
+    }
+}
+
+pub fn parse_graphics(protocol: &str) -> Result<GraphicsProtocol, String> {
+    match protocol {
+        "sixel" => Ok(GraphicsProtocol::Sixel),
+        "kitty" => Ok(GraphicsProtocol::Kitty),
+        _ => Err(format!("unknown graphics protocol: {}", protocol)),
+    }
+}
+
+fn parse_numbers<const N: usize>(args: &[&str]) -> Result<[f64; N], String> {
+    if args.len() != N {
+        return Err(format!("expected {} number(s), got {}", N, args.len()));
+    }
+
+    let mut numbers = [0.0; N];
+    for (number, arg) in numbers.iter_mut().zip(args) {
+        *number = arg
+            .parse()
+            .ok()
+            .filter(|number: &f64| number.is_finite())
+            .ok_or_else(|| format!("'{}' is not a number", arg))?;
+    }
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_line(console: &mut Console, line: &str) -> Option<String> {
+        console.open();
+        for c in line.chars() {
+            console.handle_input(ConsoleInput::Char(c));
+        }
+        console.handle_input(ConsoleInput::Submit)
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(parse_command("fov 60"), Ok(Action::SetFov(fov)) if fov == 60.0));
+        assert!(matches!(
+            parse_command("tp 1 -2 3.5"),
+            Ok(Action::Teleport(pos)) if (pos.x, pos.y, pos.z) == (1.0, -2.0, 3.5)
+        ));
+        assert!(matches!(
+            parse_command("mode raycast"),
+            Ok(Action::ChangeRenderMode(RenderMode::Raycast))
+        ));
+        assert!(matches!(
+            parse_command("shadows soft 8"),
+            Ok(Action::ChangeShadows(Shadows::Soft(8)))
+        ));
+        assert!(matches!(
+            parse_command("load my scene.scene"),
+            Ok(Action::Load(path)) if path.as_os_str() == "my scene.scene"
+        ));
+        assert!(matches!(
+            parse_command("render out.png 64x32 4"),
+            Ok(Action::Render(headless))
+                if headless.size == Some((64, 32)) && headless.samples == 4
+        ));
+        assert!(matches!(parse_command("q"), Ok(Action::Quit)));
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        for line in [
+            "tp nan 0 0",
+            "tp 0 inf 0",
+            "tp 1 2",
+            "speed nan",
+            "speed -1",
+            "zoom inf",
+            "eyes nan",
+            "convergence -inf",
+            "fov 180",
+            "fov nan",
+            "projection fisheye nan",
+            "shadows soft nan",
+            "shadows soft 2.5",
+            "shadows soft 0",
+            "render",
+            "render out.png 0",
+            "render out.png 100000x100000",
+            "mode wireframe",
+            "jump",
+        ] {
+            assert!(parse_command(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn completes_commands_and_arguments() {
+        let mut console = Console::default();
+        console.open();
+        for c in "ma".chars() {
+            console.handle_input(ConsoleInput::Char(c));
+        }
+        console.handle_input(ConsoleInput::Complete);
+        assert_eq!(console.input, "marker ");
+
+        // Only extends to the prefix shared by all candidates
+        console.handle_input(ConsoleInput::Char('b'));
+        console.handle_input(ConsoleInput::Complete);
+        assert_eq!(console.input, "marker b");
+        console.handle_input(ConsoleInput::Char('r'));
+        console.handle_input(ConsoleInput::Complete);
+        assert_eq!(console.input, "marker braille ");
+    }
+
+    #[test]
+    fn walks_history() {
+        let mut console = Console::default();
+        assert_eq!(
+            type_line(&mut console, " fov 60 "),
+            Some(String::from("fov 60"))
+        );
+        type_line(&mut console, "speed 2");
+        type_line(&mut console, "speed 2");
+        assert_eq!(type_line(&mut console, "  "), None);
+        assert_eq!(console.history, ["fov 60", "speed 2"]);
+
+        console.open();
+        console.handle_input(ConsoleInput::HistoryPrevious);
+        assert_eq!(console.input, "speed 2");
+        console.handle_input(ConsoleInput::HistoryPrevious);
+        console.handle_input(ConsoleInput::HistoryPrevious);
+        assert_eq!(console.input, "fov 60");
+        console.handle_input(ConsoleInput::HistoryNext);
+        assert_eq!(console.input, "speed 2");
+        console.handle_input(ConsoleInput::HistoryNext);
+        assert_eq!(console.input, "");
+    }
+}
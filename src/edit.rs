@@ -0,0 +1,281 @@
+use crate::mesh::Mesh;
+use crate::types::Vec3;
+
+use color_eyre::eyre::{eyre, Result};
+use std::collections::{HashMap, HashSet};
+
+// Changes kept for undo, older ones are dropped
+const HISTORY: usize = 100;
+
+// Edits the vertices and edges of a mesh in its own coordinates. Every change
+// keeps a copy of the mesh from before it, for undo and redo.
+#[derive(Debug, Clone, Default)]
+pub struct MeshEditor {
+    pub selection: Vec<usize>, // vertices, in the order they were selected
+    undo: Vec<Mesh>,
+    redo: Vec<Mesh>,
+}
+impl MeshEditor {
+    pub fn toggle(&mut self, vertex: usize) {
+        match self.selection.iter().position(|&v| v == vertex) {
+            Some(i) => {
+                self.selection.remove(i);
+            }
+            None => self.selection.push(vertex),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, mesh: &mut Mesh) -> bool {
+        let Some(previous) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(std::mem::replace(mesh, previous));
+        self.selection.retain(|&v| v < mesh.vertices.len());
+        true
+    }
+
+    pub fn redo(&mut self, mesh: &mut Mesh) -> bool {
+        let Some(next) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(std::mem::replace(mesh, next));
+        self.selection.retain(|&v| v < mesh.vertices.len());
+        true
+    }
+
+    // Keeps the mesh as it is before a change. It no longer matches the file
+    // it was loaded from, if any.
+    fn record(&mut self, mesh: &mut Mesh) {
+        if self.undo.len() == HISTORY {
+            self.undo.remove(0);
+        }
+        self.undo.push(mesh.clone());
+        self.redo.clear();
+        mesh.path = None;
+    }
+
+    pub fn move_selected(&mut self, mesh: &mut Mesh, offset: Vec3) -> Result<()> {
+        if self.selection.is_empty() {
+            return Err(eyre!("no vertices selected"));
+        }
+        self.record(mesh);
+        for &vertex in &self.selection {
+            mesh.vertices[vertex] = mesh.vertices[vertex] + offset;
+        }
+        Ok(())
+    }
+
+    pub fn add_edge(&mut self, mesh: &mut Mesh) -> Result<()> {
+        let edge = self.selected_pair("connect")?;
+        if find_edge(mesh, edge).is_some() {
+            return Err(eyre!("the vertices are already connected"));
+        }
+        self.record(mesh);
+        mesh.edges.push(edge);
+        Ok(())
+    }
+
+    // Faces along the edge go with it
+    pub fn delete_edge(&mut self, mesh: &mut Mesh) -> Result<()> {
+        let (a, b) = self.selected_pair("disconnect")?;
+        let Some(index) = find_edge(mesh, (a, b)) else {
+            return Err(eyre!("the vertices aren't connected"));
+        };
+        self.record(mesh);
+        mesh.edges.remove(index);
+        let keep: Vec<bool> = mesh
+            .faces
+            .iter()
+            .map(|face| !(face.contains(&a) && face.contains(&b)))
+            .collect();
+        let mut kept = keep.iter();
+        mesh.faces.retain(|_| *kept.next().unwrap());
+        let mut kept = keep.iter();
+        mesh.face_uvs.retain(|_| *kept.next().unwrap());
+        Ok(())
+    }
+
+    // Copies the selected vertices with the edges and faces between them and
+    // moves the copies by `offset`, connecting every copy to its original. The
+    // outline of the selected faces is closed with new side faces.
+    pub fn extrude(&mut self, mesh: &mut Mesh, offset: Vec3) -> Result<()> {
+        if self.selection.is_empty() {
+            return Err(eyre!("no vertices selected"));
+        }
+        self.record(mesh);
+
+        let copies: HashMap<usize, usize> = self
+            .selection
+            .iter()
+            .enumerate()
+            .map(|(n, &vertex)| (vertex, mesh.vertices.len() + n))
+            .collect();
+        for &vertex in &self.selection {
+            mesh.vertices.push(mesh.vertices[vertex] + offset);
+            mesh.edges.push((vertex, copies[&vertex]));
+        }
+
+        // The selected faces move to the copies, edges between two of them
+        // are inside the extruded part and get no side faces
+        let mut inner_edges = HashMap::new();
+        for face in &mut mesh.faces {
+            if face.iter().all(|vertex| copies.contains_key(vertex)) {
+                for n in 0..3 {
+                    let (a, b) = (face[n], face[(n + 1) % 3]);
+                    *inner_edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+                }
+                *face = face.map(|vertex| copies[&vertex]);
+            }
+        }
+
+        for i in 0..mesh.edges.len() {
+            let (a, b) = mesh.edges[i];
+            let (Some(&ca), Some(&cb)) = (copies.get(&a), copies.get(&b)) else {
+                continue;
+            };
+            mesh.edges.push((ca, cb));
+            if inner_edges.get(&(a.min(b), a.max(b))).copied().unwrap_or(0) < 2 {
+                mesh.faces.push([a, b, cb]);
+                mesh.faces.push([a, cb, ca]);
+            }
+        }
+        // The side faces have no texture coordinates to go with them
+        if mesh.face_uvs.len() != mesh.faces.len() {
+            mesh.face_uvs.clear();
+        }
+
+        self.selection = self.selection.iter().map(|vertex| copies[vertex]).collect();
+        Ok(())
+    }
+
+    // Joins the selected vertices into the first one, at their center
+    pub fn merge(&mut self, mesh: &mut Mesh) -> Result<()> {
+        if self.selection.len() < 2 {
+            return Err(eyre!("select at least two vertices to merge"));
+        }
+        let target = self.selection[0];
+        let merged: HashSet<usize> = self.selection[1..].iter().copied().collect();
+        self.record(mesh);
+
+        let center = self
+            .selection
+            .iter()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &v| sum + mesh.vertices[v])
+            * (1.0 / self.selection.len() as f64);
+        mesh.vertices[target] = center;
+
+        // Indices after removing the merged vertices
+        let mut index = vec![0; mesh.vertices.len()];
+        let mut vertices = Vec::new();
+        for (i, &vertex) in mesh.vertices.iter().enumerate() {
+            if !merged.contains(&i) {
+                index[i] = vertices.len();
+                vertices.push(vertex);
+            }
+        }
+        for &i in &merged {
+            index[i] = index[target];
+        }
+
+        let mut seen = HashSet::new();
+        let edges = mesh
+            .edges
+            .iter()
+            .map(|&(a, b)| (index[a], index[b]))
+            .filter(|&(a, b)| a != b && seen.insert((a.min(b), a.max(b))))
+            .collect();
+        let (faces, face_uvs) = mesh
+            .faces
+            .iter()
+            .enumerate()
+            .map(|(i, face)| (face.map(|vertex| index[vertex]), mesh.face_uvs.get(i)))
+            .filter(|([a, b, c], _)| a != b && b != c && c != a)
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+
+        mesh.vertices = vertices;
+        mesh.edges = edges;
+        mesh.faces = faces;
+        mesh.face_uvs = face_uvs.into_iter().flatten().copied().collect();
+        self.selection = vec![index[target]];
+        Ok(())
+    }
+
+    fn selected_pair(&self, what: &str) -> Result<(usize, usize)> {
+        match *self.selection.as_slice() {
+            [a, b] => Ok((a, b)),
+            _ => Err(eyre!("select two vertices to {}", what)),
+        }
+    }
+}
+
+fn find_edge(mesh: &Mesh, (a, b): (usize, usize)) -> Option<usize> {
+    mesh.edges
+        .iter()
+        .position(|&edge| edge == (a, b) || edge == (b, a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extrude_face_and_undo() {
+        let mut mesh = Mesh::cube();
+        let mut editor = MeshEditor::default();
+        for vertex in [0, 1, 2, 3] {
+            editor.toggle(vertex);
+        }
+        editor.extrude(&mut mesh, Vec3::new(0.0, 0.0, 0.1)).unwrap();
+
+        assert_eq!(mesh.vertices.len(), 12);
+        assert_eq!(mesh.edges.len(), 12 + 4 + 4);
+        // the front face moved out and four sides were added
+        assert_eq!(mesh.faces.len(), 12 + 8);
+        assert_eq!(editor.selection, vec![8, 9, 10, 11]);
+
+        assert!(editor.undo(&mut mesh));
+        assert_eq!(mesh.vertices.len(), 8);
+        assert!(mesh.is_builtin());
+        assert!(editor.redo(&mut mesh));
+        assert_eq!(mesh.vertices.len(), 12);
+    }
+
+    #[test]
+    fn merge_removes_edges_and_faces_between() {
+        let mut mesh = Mesh::cube();
+        let mut editor = MeshEditor::default();
+        editor.toggle(0);
+        editor.toggle(1);
+        editor.merge(&mut mesh).unwrap();
+
+        assert_eq!(mesh.vertices.len(), 7);
+        assert_eq!(mesh.edges.len(), 11);
+        assert_eq!(mesh.faces.len(), 10);
+        assert_eq!(mesh.vertices[0], Vec3::new(0.0, -0.1, 0.1));
+        assert!(mesh.edges.iter().all(|&(a, b)| a < 7 && b < 7));
+    }
+
+    #[test]
+    fn edges_need_two_vertices() {
+        let mut mesh = Mesh::cube();
+        let mut editor = MeshEditor::default();
+        editor.toggle(0);
+        assert!(editor.add_edge(&mut mesh).is_err());
+
+        editor.toggle(6);
+        editor.add_edge(&mut mesh).unwrap();
+        assert_eq!(mesh.edges.len(), 13);
+        editor.delete_edge(&mut mesh).unwrap();
+        assert_eq!(mesh.edges.len(), 12);
+        assert!(!editor.can_redo());
+        assert!(editor.can_undo());
+    }
+}
@@ -0,0 +1,93 @@
+use crate::color;
+use crate::render::Segment;
+
+// Pixel buffer everything is rasterized into before an output backend maps it
+// to terminal cells. The resolution is a multiple of the cell grid, depending
+// on how many sub-cell pixels the backend can show.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub background: [u8; 3],
+    pub color: Vec<[u8; 3]>,
+    pub depth: Vec<f64>,
+    pub coverage: Vec<f32>, // 0.0 is empty background, 1.0 is fully drawn
+}
+impl Framebuffer {
+    pub fn new(width: usize, height: usize, background: [u8; 3]) -> Self {
+        Self {
+            width,
+            height,
+            background,
+            color: vec![background; width * height],
+            depth: vec![f64::INFINITY; width * height],
+            coverage: vec![0.0; width * height],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.color.fill(self.background);
+        self.depth.fill(f64::INFINITY);
+        self.coverage.fill(0.0);
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    // Draws a pixel when it is closer than what is already there, blending
+    // partially covered pixels over the existing color
+    pub fn plot(&mut self, x: i64, y: i64, depth: f64, color: [u8; 3], coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let i = self.index(x as usize, y as usize);
+        if depth > self.depth[i] {
+            return;
+        }
+
+        let coverage = coverage.clamp(0.0, 1.0);
+        self.color[i] = blend(self.color[i], color, coverage);
+        self.coverage[i] = self.coverage[i].max(coverage);
+        if coverage >= 1.0 {
+            self.depth[i] = depth;
+        }
+    }
+
+    // Maps normalized device coordinates to pixel coordinates, y pointing down
+    pub fn to_pixel(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x + 1.0) / 2.0 * (self.width as f64 - 1.0),
+            (1.0 - y) / 2.0 * (self.height as f64 - 1.0),
+        )
+    }
+
+    pub fn draw_segment(&mut self, segment: &Segment) {
+        let color = color::to_rgb(segment.color, [255, 255, 255]);
+        let (x1, y1) = self.to_pixel(segment.x1, segment.y1);
+        let (x2, y2) = self.to_pixel(segment.x2, segment.y2);
+
+        let steps = (x2 - x1).abs().max((y2 - y1).abs()).ceil().max(1.0);
+        for step in 0..=steps as i64 {
+            let t = step as f64 / steps;
+            // depth is linear in screen space only after inverting it
+            let depth = 1.0 / (1.0 / segment.z1 + (1.0 / segment.z2 - 1.0 / segment.z1) * t);
+            self.plot(
+                (x1 + (x2 - x1) * t).round() as i64,
+                (y1 + (y2 - y1) * t).round() as i64,
+                depth,
+                color,
+                segment.opacity,
+            );
+        }
+    }
+}
+
+pub fn blend(under: [u8; 3], over: [u8; 3], alpha: f32) -> [u8; 3] {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * alpha).round() as u8;
+    [
+        mix(under[0], over[0]),
+        mix(under[1], over[1]),
+        mix(under[2], over[2]),
+    ]
+}
@@ -0,0 +1,250 @@
+use crate::camera::Camera;
+use crate::render::{self, Segment};
+use crate::scene::{Object, Transform};
+use crate::types::Vec3;
+
+use ratatui::style::Color;
+use std::f64::consts::TAU;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+impl Axis {
+    pub fn unit(self) -> Vec3 {
+        match self {
+            Axis::X => Vec3::new(1.0, 0.0, 0.0),
+            Axis::Y => Vec3::new(0.0, 1.0, 0.0),
+            Axis::Z => Vec3::new(0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Axis::X => Color::LightRed,
+            Axis::Y => Color::LightGreen,
+            Axis::Z => Color::LightBlue,
+        }
+    }
+
+    // The component of a vector along this axis
+    fn of(self, v: &mut Vec3) -> &mut f64 {
+        match self {
+            Axis::X => &mut v.x,
+            Axis::Y => &mut v.y,
+            Axis::Z => &mut v.z,
+        }
+    }
+
+    // The other two axes, in order
+    fn others(self) -> (Axis, Axis) {
+        match self {
+            Axis::X => (Axis::Y, Axis::Z),
+            Axis::Y => (Axis::Z, Axis::X),
+            Axis::Z => (Axis::X, Axis::Y),
+        }
+    }
+}
+
+// Moves, rotates or scales an object along one world axis. The axes are drawn
+// at the origin of the object, with a handle on the active one that shows the
+// mode: an arrow for translating, a ring for rotating and a box for scaling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub axis: Axis,
+    pub snap: bool, // to multiples of `step`
+}
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            axis: Axis::X,
+            snap: true,
+        }
+    }
+}
+impl Gizmo {
+    // In world units, degrees and scale factor
+    pub fn step(&self) -> f64 {
+        match self.mode {
+            GizmoMode::Translate => 0.25,
+            GizmoMode::Rotate => 15.0,
+            GizmoMode::Scale => 0.25,
+        }
+    }
+
+    pub fn apply(&self, transform: &mut Transform, amount: f64) {
+        let vector = match self.mode {
+            GizmoMode::Translate => &mut transform.position,
+            GizmoMode::Rotate => &mut transform.rotation,
+            GizmoMode::Scale => &mut transform.scale,
+        };
+        let value = self.axis.of(vector);
+        *value += amount;
+        if self.snap {
+            *value = (*value / self.step()).round() * self.step();
+        }
+        // Scaling through zero would turn the object inside out
+        if self.mode == GizmoMode::Scale {
+            *value = value.max(if self.snap { self.step() } else { 0.01 });
+        }
+    }
+
+    // How far a drag between two points in normalized device coordinates
+    // moves the object, by how far it goes along the axis on screen. Rotation
+    // follows the horizontal movement, half the screen width is 180 degrees.
+    pub fn drag_amount(
+        &self,
+        object: &Object,
+        camera: &Camera,
+        from: (f64, f64),
+        to: (f64, f64),
+    ) -> f64 {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        if self.mode == GizmoMode::Rotate {
+            return dx * 180.0;
+        }
+
+        let origin = object.transform.position;
+        let length = self.length(object);
+        let (x1, y1) = camera.project_vertex(&origin);
+        let (x2, y2) = camera.project_vertex(&(origin + self.axis.unit() * length));
+        let (ax, ay) = (x2 - x1, y2 - y1);
+        let screen_length = ax * ax + ay * ay;
+        // An axis pointing at the camera can't be dragged along
+        if screen_length < 1e-6 {
+            return 0.0;
+        }
+        (dx * ax + dy * ay) / screen_length * length
+    }
+
+    // Lines in world space, drawn over everything else
+    pub fn segments(&self, object: &Object, camera: &Camera) -> Vec<Segment> {
+        let origin = object.transform.position;
+        let length = self.length(object);
+
+        let mut lines = Vec::new();
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            lines.push((origin, origin + axis.unit() * length, axis.color()));
+        }
+
+        let color = self.axis.color();
+        let direction = self.axis.unit();
+        let (u, v) = self.axis.others();
+        let (u, v) = (u.unit(), v.unit());
+        let tip = origin + direction * length;
+        match self.mode {
+            GizmoMode::Translate => {
+                let base = tip - direction * (length * 0.2);
+                for side in [u, v] {
+                    lines.push((tip, base + side * (length * 0.08), color));
+                    lines.push((tip, base - side * (length * 0.08), color));
+                }
+            }
+            GizmoMode::Rotate => {
+                let radius = length * 0.8;
+                let point = |n: usize| {
+                    let angle = n as f64 / 24.0 * TAU;
+                    origin + u * (angle.cos() * radius) + v * (angle.sin() * radius)
+                };
+                lines.extend((0..24).map(|n| (point(n), point(n + 1), color)));
+            }
+            GizmoMode::Scale => {
+                let size = length * 0.08;
+                let corners = [u + v, u - v, u * -1.0 - v, v - u].map(|c| tip + c * size);
+                lines.extend((0..4).map(|n| (corners[n], corners[(n + 1) % 4], color)));
+            }
+        }
+
+        lines
+            .into_iter()
+            .flat_map(|(start, end, color)| render::project_line(camera, start, end, color))
+            .map(|segment| Segment {
+                z1: camera.near_plane,
+                z2: camera.near_plane,
+                ..segment
+            })
+            .collect()
+    }
+
+    // Reaches a bit past the object, so the axes stay visible
+    fn length(&self, object: &Object) -> f64 {
+        let origin = object.transform.position;
+        let radius = object
+            .world_vertices()
+            .iter()
+            .map(|&vertex| (vertex - origin).length())
+            .fold(0.0, f64::max);
+        (radius * 1.3).max(0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Mesh;
+
+    #[test]
+    fn apply_snaps_to_steps() {
+        let mut transform = Transform::default();
+        let gizmo = Gizmo::default();
+        gizmo.apply(&mut transform, 0.3);
+        assert_eq!(transform.position.x, 0.25);
+
+        let gizmo = Gizmo {
+            mode: GizmoMode::Rotate,
+            axis: Axis::Y,
+            snap: true,
+        };
+        gizmo.apply(&mut transform, 40.0);
+        assert_eq!(transform.rotation.y, 45.0);
+
+        let gizmo = Gizmo {
+            mode: GizmoMode::Scale,
+            axis: Axis::Z,
+            snap: false,
+        };
+        gizmo.apply(&mut transform, -5.0);
+        assert_eq!(transform.scale.z, 0.01);
+    }
+
+    #[test]
+    fn drag_along_axis_on_screen() {
+        let object = Object::new(Mesh::cube());
+        let camera = Camera {
+            pos: Vec3::new(0.0, 0.0, -5.0),
+            aspect_ratio: 1.0,
+            ..Default::default()
+        };
+        let gizmo = Gizmo::default();
+
+        // the x axis points right, the y axis up
+        let (x, _) = camera.project_vertex(&Vec3::new(1.0, 0.0, 0.0));
+        let amount = gizmo.drag_amount(&object, &camera, (0.0, 0.0), (x, 0.0));
+        assert!((amount - 1.0).abs() < 1e-9);
+        assert_eq!(
+            gizmo.drag_amount(&object, &camera, (0.0, 0.0), (0.0, 0.5)),
+            0.0
+        );
+
+        // the z axis points straight away from the camera
+        let gizmo = Gizmo {
+            axis: Axis::Z,
+            ..Default::default()
+        };
+        assert_eq!(
+            gizmo.drag_amount(&object, &camera, (0.0, 0.0), (0.5, 0.5)),
+            0.0
+        );
+    }
+}
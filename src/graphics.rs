@@ -0,0 +1,285 @@
+use crate::framebuffer::Framebuffer;
+use crate::output::OutputBackend;
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Color};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphicsProtocol {
+    Sixel,
+    Kitty,
+}
+impl GraphicsProtocol {
+    pub fn detect() -> Option<Self> {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        Self::from_env(
+            &var("TERM"),
+            &var("TERM_PROGRAM"),
+            !var("KITTY_WINDOW_ID").is_empty(),
+        )
+    }
+
+    // Terminals don't announce graphics support in the environment, so this
+    // only recognizes the ones known to support it
+    pub fn from_env(term: &str, term_program: &str, kitty_window: bool) -> Option<Self> {
+        if kitty_window
+            || term == "xterm-kitty"
+            || term == "xterm-ghostty"
+            || matches!(term_program, "WezTerm" | "ghostty")
+        {
+            Some(GraphicsProtocol::Kitty)
+        } else if term.contains("sixel")
+            || ["foot", "mlterm", "contour", "yaft"]
+                .iter()
+                .any(|name| term.starts_with(name))
+        {
+            Some(GraphicsProtocol::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+// Draws the framebuffer as an actual bitmap at the pixel size of the cells.
+// The escape sequence is written as the symbol of the top left cell and every
+// other cell of the area is skipped, so the image isn't drawn over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphicsBackend {
+    pub protocol: GraphicsProtocol,
+    pub cell_width: usize,
+    pub cell_height: usize,
+}
+impl GraphicsBackend {
+    // The cell size is measured once by the caller, see
+    // `metrics::cell_pixel_size`, since asking the terminal can take a while
+    pub fn with_cell_size(
+        protocol: GraphicsProtocol,
+        (cell_width, cell_height): (usize, usize),
+    ) -> Self {
+        Self {
+            protocol,
+            cell_width,
+            cell_height,
+        }
+    }
+
+    pub fn encode(&self, framebuffer: &Framebuffer, area: Rect, background: Color) -> String {
+        // The terminal's own background shows through empty pixels
+        let transparent = background == Color::Reset;
+        match self.protocol {
+            GraphicsProtocol::Sixel => encode_sixel(framebuffer, transparent),
+            GraphicsProtocol::Kitty => encode_kitty(framebuffer, area, transparent),
+        }
+    }
+}
+impl OutputBackend for GraphicsBackend {
+    fn cell_size(&self) -> (usize, usize) {
+        (self.cell_width, self.cell_height)
+    }
+
+    fn render(&self, framebuffer: &Framebuffer, area: Rect, buf: &mut Buffer, background: Color) {
+        let image = self.encode(framebuffer, area, background);
+        for position in area.positions() {
+            let cell = &mut buf[position];
+            cell.reset();
+            cell.set_skip(true);
+        }
+        buf[(area.x, area.y)].set_skip(false).set_symbol(&image);
+    }
+}
+
+// Removes the images kitty keeps showing independently of the text cells
+pub const KITTY_DELETE_ALL: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+pub fn encode_sixel(framebuffer: &Framebuffer, transparent: bool) -> String {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+
+    // Colors are reduced to a 6x6x6 cube to stay within the 256 registers
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let pixels: Vec<Option<u32>> = (0..width * height)
+        .map(|i| {
+            if transparent && framebuffer.coverage[i] <= 0.0 {
+                return None;
+            }
+            let [r, g, b] = framebuffer.color[i];
+            Some(level(r) * 36 + level(g) * 6 + level(b))
+        })
+        .collect();
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "\x1bP0;{};0q\"1;1;{};{}",
+        transparent as u8, width, height
+    );
+    for color in pixels.iter().flatten().collect::<BTreeSet<_>>() {
+        let percent = |level: u32| level * 20;
+        let _ = write!(
+            out,
+            "#{};2;{};{};{}",
+            color,
+            percent(color / 36),
+            percent(color / 6 % 6),
+            percent(color % 6)
+        );
+    }
+
+    // Every sixel character holds a column of 6 pixels, each color of a band
+    // is drawn over the same line
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let colors: BTreeSet<u32> = rows
+            .clone()
+            .flat_map(|y| pixels[y * width..(y + 1) * width].iter().flatten().copied())
+            .collect();
+
+        for (n, &color) in colors.iter().enumerate() {
+            if n > 0 {
+                out.push('$');
+            }
+            let _ = write!(out, "#{}", color);
+
+            let mut line: Vec<char> = (0..width)
+                .map(|x| {
+                    let bits = rows
+                        .clone()
+                        .filter(|&y| pixels[y * width + x] == Some(color))
+                        .fold(0, |bits, y| bits | 1 << (y - band));
+                    char::from(63 + bits as u8)
+                })
+                .collect();
+            while line.last() == Some(&'?') {
+                line.pop();
+            }
+            push_runs(&mut out, &line);
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+// Run length encodes repeated sixel characters
+fn push_runs(out: &mut String, line: &[char]) {
+    let mut i = 0;
+    while i < line.len() {
+        let run = line[i..].iter().take_while(|&&c| c == line[i]).count();
+        if run > 3 {
+            let _ = write!(out, "!{}{}", run, line[i]);
+        } else {
+            out.extend(std::iter::repeat_n(line[i], run));
+        }
+        i += run;
+    }
+}
+
+pub fn encode_kitty(framebuffer: &Framebuffer, area: Rect, transparent: bool) -> String {
+    // Every viewport replaces only its own image
+    let id = ((area.y as u32) << 16 | area.x as u32) + 1;
+
+    let mut data = Vec::with_capacity(framebuffer.width * framebuffer.height * 4);
+    for (i, color) in framebuffer.color.iter().enumerate() {
+        let alpha = if transparent && framebuffer.coverage[i] <= 0.0 {
+            0
+        } else {
+            255
+        };
+        data.extend_from_slice(color);
+        data.push(alpha);
+    }
+    let encoded = base64(&data);
+
+    let mut out = String::new();
+    let _ = write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", id);
+
+    // Payloads are sent in chunks of at most 4096 bytes
+    let chunks: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(4096)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    for (n, chunk) in chunks.iter().enumerate() {
+        let more = (n + 1 < chunks.len()) as u8;
+        if n == 0 {
+            let _ = write!(
+                out,
+                "\x1b_Ga=T,f=32,s={},v={},c={},r={},i={},C=1,q=2,m={};{}\x1b\\",
+                framebuffer.width, framebuffer.height, area.width, area.height, id, more, chunk
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    out
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for n in 0..4 {
+            if n <= chunk.len() {
+                out.push(ALPHABET[(triple >> (18 - n * 6) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_vectors() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn detects_protocol_from_env() {
+        let detect = GraphicsProtocol::from_env;
+        assert_eq!(
+            detect("xterm-kitty", "", false),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            detect("xterm-256color", "WezTerm", false),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            detect("xterm-256color", "", true),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(detect("foot", "", false), Some(GraphicsProtocol::Sixel));
+        assert_eq!(detect("xterm-256color", "", false), None);
+    }
+
+    #[test]
+    fn sixel_encodes_bands_and_runs() {
+        let mut framebuffer = Framebuffer::new(8, 7, [0, 0, 0]);
+        for x in 0..8 {
+            framebuffer.plot(x, 0, 1.0, [255, 0, 0], 1.0);
+        }
+        framebuffer.plot(0, 6, 1.0, [255, 255, 255], 1.0);
+
+        assert_eq!(
+            encode_sixel(&framebuffer, true),
+            "\x1bP0;1;0q\"1;1;8;7#180;2;100;0;0#215;2;100;100;100#180!8@-#215@-\x1b\\"
+        );
+    }
+}
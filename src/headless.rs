@@ -0,0 +1,275 @@
+use crate::framebuffer::Framebuffer;
+use crate::viewport::{Viewport3D, ViewportState};
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use ratatui::{
+    buffer::Buffer,
+    crossterm::style::{ResetColor, SetBackgroundColor, SetForegroundColor},
+    layout::Rect,
+    widgets::StatefulWidget,
+};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Headless {
+    pub output: PathBuf,
+    pub size: Option<(u32, u32)>, // pixels for images, cells for text
+    pub samples: usize,           // paths per pixel in the path trace mode
+}
+
+// Paths per pixel when rendering to a file, unless asked for more or less
+pub const DEFAULT_SAMPLES: usize = 32;
+
+pub fn render_to_file(
+    headless: &Headless,
+    viewport: Viewport3D,
+    state: &mut ViewportState,
+) -> Result<()> {
+    let path = &headless.output;
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !["ppm", "png", "txt", "ans", "ansi"].contains(&extension.as_str()) {
+        return Err(eyre!(
+            "unsupported output format '{}', use ppm, png, txt or ans",
+            extension
+        ));
+    }
+
+    let viewport = viewport.samples(headless.samples);
+    let file =
+        File::create(path).wrap_err_with(|| format!("could not create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    match extension.as_str() {
+        "ppm" | "png" => {
+            let (width, height) = headless.size.unwrap_or((640, 480));
+            let image = viewport.render_image(state, width, height);
+            if extension == "ppm" {
+                image.write_ppm(&mut writer)?;
+            } else {
+                image.write_png(&mut writer)?;
+            }
+        }
+        _ => {
+            let (cols, rows) = headless.size.unwrap_or((80, 24));
+            let (cols, rows) = (cols.min(u16::MAX as u32), rows.min(u16::MAX as u32));
+            writer.write_all(render_ansi(viewport, state, cols as u16, rows as u16).as_bytes())?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn render_ansi(
+    viewport: Viewport3D,
+    state: &mut ViewportState,
+    cols: u16,
+    rows: u16,
+) -> String {
+    let area = Rect::new(0, 0, cols, rows);
+    let mut buffer = Buffer::empty(area);
+    viewport.render(area, &mut buffer, state);
+
+    buffer_to_ansi(&buffer)
+}
+
+pub fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let mut out = String::new();
+    let area = buffer.area;
+
+    for y in area.top()..area.bottom() {
+        let mut fg = None;
+        let mut bg = None;
+        for x in area.left()..area.right() {
+            let cell = &buffer[(x, y)];
+            if fg != Some(cell.fg) {
+                fg = Some(cell.fg);
+                let _ = write!(out, "{}", SetForegroundColor(cell.fg.into()));
+            }
+            if bg != Some(cell.bg) {
+                bg = Some(cell.bg);
+                let _ = write!(out, "{}", SetBackgroundColor(cell.bg.into()));
+            }
+            out.push_str(cell.symbol());
+        }
+        let _ = writeln!(out, "{}", ResetColor);
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+impl Image {
+    pub fn from_framebuffer(framebuffer: &Framebuffer) -> Self {
+        Self {
+            width: framebuffer.width as u32,
+            height: framebuffer.height as u32,
+            pixels: framebuffer.color.clone(),
+        }
+    }
+
+    // Reads a PPM, PGM or PNG file, grayscale images become gray pixels
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).wrap_err_with(|| format!("could not read {}", path.display()))?;
+        let is_png = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        let image = if is_png {
+            Self::parse_png(&bytes)
+        } else {
+            Self::parse_pnm(&bytes)
+        };
+        image.wrap_err_with(|| format!("in {}", path.display()))
+    }
+
+    // The binary (P5, P6) and plain (P2, P3) variants of PGM and PPM
+    pub fn parse_pnm(bytes: &[u8]) -> Result<Self> {
+        let mut position = 0;
+        let mut token = || pnm_token(bytes, &mut position);
+        let magic = token()?.to_vec();
+        let (channels, binary) = match magic.as_slice() {
+            b"P2" => (1, false),
+            b"P3" => (3, false),
+            b"P5" => (1, true),
+            b"P6" => (3, true),
+            _ => return Err(eyre!("not a PPM or PGM image")),
+        };
+        let mut number = || pnm_number(bytes, &mut position);
+        let (width, height, max) = (number()?, number()?, number()?);
+        if max == 0 || max > 65535 {
+            return Err(eyre!("invalid maximum value {}", max));
+        }
+
+        let too_large = || eyre!("image is too large");
+        if u32::try_from(width).is_err() || u32::try_from(height).is_err() {
+            return Err(too_large());
+        }
+        let count = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .ok_or_else(too_large)?;
+        let values: Vec<usize> = if binary {
+            // A single whitespace separates the header from the data
+            let start = position + 1;
+            let size = if max > 255 { 2 } else { 1 };
+            let end = count
+                .checked_mul(size)
+                .and_then(|length| length.checked_add(start))
+                .ok_or_else(too_large)?;
+            let data = bytes
+                .get(start..end)
+                .ok_or_else(|| eyre!("image data is cut short"))?;
+            data.chunks(size)
+                .map(|chunk| chunk.iter().fold(0, |value, &b| value << 8 | b as usize))
+                .collect()
+        } else {
+            (0..count)
+                .map(|_| pnm_number(bytes, &mut position))
+                .collect::<Result<_>>()?
+        };
+
+        let scale = |value: usize| (value.min(max) * 255 / max) as u8;
+        let pixels = values
+            .chunks(channels)
+            .map(|pixel| match *pixel {
+                [gray] => [scale(gray); 3],
+                [r, g, b] => [scale(r), scale(g), scale(b)],
+                _ => unreachable!(),
+            })
+            .collect();
+        Ok(Self {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+        })
+    }
+
+    // Transparency is dropped
+    pub fn parse_png(bytes: &[u8]) -> Result<Self> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let size = reader
+            .output_buffer_size()
+            .ok_or_else(|| eyre!("image is too large"))?;
+        let mut data = vec![0; size];
+        let info = reader.next_frame(&mut data)?;
+
+        let channels = info.color_type.samples();
+        let pixels = data[..info.line_size * info.height as usize]
+            .chunks(info.line_size)
+            .flat_map(|line| line[..info.width as usize * channels].chunks(channels))
+            .map(|pixel| match pixel.len() {
+                1 | 2 => [pixel[0]; 3],
+                _ => [pixel[0], pixel[1], pixel[2]],
+            })
+            .collect();
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    pub fn write_ppm(&self, writer: &mut impl Write) -> Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(self.pixels.as_flattened())?;
+        Ok(())
+    }
+
+    pub fn write_png(&self, writer: &mut impl Write) -> Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut png_writer = encoder.write_header()?;
+        png_writer.write_image_data(self.pixels.as_flattened())?;
+        png_writer.finish()?;
+        Ok(())
+    }
+}
+
+// Whitespace separated words, with comments from # to the end of the line
+fn pnm_token<'a>(bytes: &'a [u8], position: &mut usize) -> Result<&'a [u8]> {
+    loop {
+        match bytes.get(*position) {
+            Some(b'#') => {
+                while bytes.get(*position).is_some_and(|&b| b != b'\n') {
+                    *position += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *position += 1,
+            Some(_) => break,
+            None => return Err(eyre!("unexpected end of file")),
+        }
+    }
+    let start = *position;
+    while bytes
+        .get(*position)
+        .is_some_and(|b| !b.is_ascii_whitespace())
+    {
+        *position += 1;
+    }
+    Ok(&bytes[start..*position])
+}
+
+fn pnm_number(bytes: &[u8], position: &mut usize) -> Result<usize> {
+    let token = pnm_token(bytes, position)?;
+    std::str::from_utf8(token)
+        .ok()
+        .and_then(|token| token.parse().ok())
+        .ok_or_else(|| eyre!("invalid number '{}'", String::from_utf8_lossy(token)))
+}
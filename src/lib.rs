@@ -0,0 +1,42 @@
+//! A 3d renderer for the terminal, built on ratatui.
+//!
+//! [`Viewport3D`] renders a [`Scene`] as seen from the [`Camera`] in its
+//! [`ViewportState`] into any area of a ratatui buffer.
+
+pub mod camera;
+pub mod color;
+pub mod edit;
+pub mod framebuffer;
+pub mod gizmo;
+pub mod graphics;
+pub mod headless;
+pub mod material;
+pub mod mesh;
+pub mod metrics;
+pub mod output;
+pub mod render;
+pub mod scene;
+pub mod sdf;
+pub mod stereo;
+pub mod texture;
+pub mod types;
+pub mod viewport;
+
+#[cfg(test)]
+mod snapshot_tests;
+
+pub use camera::{Camera, Projection, ViewPreset};
+pub use color::{ColorSupport, Dither};
+pub use edit::MeshEditor;
+pub use framebuffer::Framebuffer;
+pub use gizmo::{Axis, Gizmo, GizmoMode};
+pub use material::Material;
+pub use mesh::Mesh;
+pub use output::{DrawMode, OutputBackend};
+pub use render::{Accumulation, HiddenLines, Shadows};
+pub use scene::{Light, Object, Scene, Transform};
+pub use sdf::{Operation, Primitive, Shape};
+pub use stereo::{Stereo, StereoMode};
+pub use texture::{Filter, Texture, Wrap};
+pub use types::{Vec2, Vec3};
+pub use viewport::{RenderMode, RenderStats, Viewport3D, ViewportState};
@@ -1,83 +1,45 @@
 mod app;
-mod camera;
-mod types;
+mod cli;
+mod console;
+mod ui;
 
-use app::{App, RenderMode};
-use types::MyShapes;
+use app::App;
+use console::ConsoleMessage;
 
 use color_eyre::Result;
 use crossterm::ExecutableCommand;
 
+use std::io::Write;
+use termulator::{graphics, headless};
+
 use ratatui::{
-    style::Color,
-    widgets::{
-        canvas::{Canvas, Line},
-        Paragraph,
-    },
+    layout::{Constraint, Layout},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
     DefaultTerminal,
 };
 
-fn clip_line_to_viewport(
-    x1: f64,
-    y1: f64,
-    x2: f64,
-    y2: f64,
-    xmin: f64,
-    xmax: f64,
-    ymin: f64,
-    ymax: f64,
-) -> Option<(f64, f64, f64, f64)> {
-    let dx = x2 - x1;
-    let dy = y2 - y1;
-
-    let mut t0 = 0.0;
-    let mut t1 = 1.0;
-
-    let clip = |p: f64, q: f64, t0: &mut f64, t1: &mut f64| -> bool {
-        if p == 0.0 {
-            return q >= 0.0;
-        }
-        let r = q / p;
-        if p < 0.0 {
-            if r > *t1 {
-                return false;
-            }
-            if r > *t0 {
-                *t0 = r;
-            }
-        } else {
-            if r < *t0 {
-                return false;
-            }
-            if r < *t1 {
-                *t1 = r;
-            }
-        }
-        true
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let Some(options) = cli::Options::parse(std::env::args().skip(1))? else {
+        print!("{}", cli::HELP);
+        return Ok(());
     };
+    let mut app = App::default();
+    let headless = options.headless.clone();
+    options.apply(&mut app)?;
 
-    if clip(-dx, x1 - xmin, &mut t0, &mut t1)
-        && clip(dx, xmax - x1, &mut t0, &mut t1)
-        && clip(-dy, y1 - ymin, &mut t0, &mut t1)
-        && clip(dy, ymax - y1, &mut t0, &mut t1)
-    {
-        let nx1 = x1 + t0 * dx;
-        let ny1 = y1 + t0 * dy;
-        let nx2 = x1 + t1 * dx;
-        let ny2 = y1 + t1 * dy;
-        Some((nx1, ny1, nx2, ny2))
-    } else {
-        None
+    if let Some(headless) = headless {
+        let mut state = app.viewport.clone();
+        return headless::render_to_file(&headless, app.viewport_widget(app.draw_mode), &mut state);
     }
-}
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
     std::io::stdout()
         .execute(crossterm::event::EnableMouseCapture)
         .unwrap();
     let terminal = ratatui::init();
-    let app = App::default();
 
     let result = run(app, terminal);
 
@@ -87,77 +49,86 @@ fn main() -> Result<()> {
 }
 
 fn run(mut app: App, mut terminal: DefaultTerminal) -> Result<()> {
-    let shapes = MyShapes::create_shapes();
-
+    let mut graphics_shown = false;
+    // Frames are counted over a second, events can redraw a lot faster than
+    // the target frame rate for a moment
+    let mut fps_start = std::time::Instant::now();
+    let mut frames = 0;
     loop {
+        let frame_start = std::time::Instant::now();
+        frames += 1;
+        let elapsed = frame_start.duration_since(fps_start).as_secs_f64();
+        if elapsed >= 1.0 {
+            app.fps = frames as f64 / elapsed;
+            fps_start = frame_start;
+            frames = 0;
+        }
+
+        // Images stay on screen until the cells below them are redrawn, and
+        // kitty keeps its images even then
+        if graphics_shown && !app.graphics_active() {
+            clear_graphics(&mut terminal)?;
+        }
+        graphics_shown = app.graphics_active();
+
         terminal.draw(|frame| {
-            let area = frame.area();
-            let canvas = Canvas::default()
-                .x_bounds([-1.0, 1.0])
-                .y_bounds([-1.0, 1.0])
-                .marker(app.draw_mode)
-                .background_color(Color::Blue)
-                .paint(|ctx| {
-
-                    match app.render_mode {
-                        RenderMode::Vertex => {
-                             for &(start_idx, end_idx) in &shapes._cube_edges {
-                                let start_vertex = shapes._cube_vertices[start_idx];
-                                let end_vertex = shapes._cube_vertices[end_idx];
-
-                                let start = start_vertex - app.camera.pos;
-                                let end = end_vertex - app.camera.pos;
-
-                                let (x1, y1) = app.camera.project_vertex(&start);
-                                let (x2, y2) = app.camera.project_vertex(&end);
-
-                                if let Some((x1, y1, x2, y2)) =
-                                    clip_line_to_viewport(x1, y1, x2, y2, -1.0, 1.0, -1.0, 1.0)
-                                {
-                                    let color = Color::Red;
-                                    ctx.draw(&Line {
-                                        x1,
-                                        y1,
-                                        x2,
-                                        y2,
-                                        color,
-                                    });
-                                }
-                            }       
-                        },
-                        RenderMode::Raycast => {
-                            let rows = app.terminal_size.y as i32;
-                            let cols = app.terminal_size.x as i32;
-                            for row in 0..rows {
-                                for col in 0..cols {
-                                    let u = (col as f64 / app.terminal_size.x) * 2.0 - 1.0;
-                                    let v = 1.0 - (row as f64 / app.terminal_size.y) * 2.0;
-
-                                    let ray_dir = app.camera.cast_ray(u, v);
-
-                                    // IMPLEMENT RENDERING LOGIC HERE
-                                }
-                            }
-                        }
-                    }
-                    
-                });
+            let show_console = app.console.active || app.console.message.is_some();
+            let [main_area, console_area] = Layout::vertical([
+                Constraint::Min(0),
+                Constraint::Length(if show_console { 1 } else { 0 }),
+            ])
+            .areas(frame.area());
+            let inspector_width = if app.show_inspector {
+                ui::INSPECTOR_WIDTH
+            } else {
+                0
+            };
+            let [area, inspector_area] =
+                Layout::horizontal([Constraint::Min(0), Constraint::Length(inspector_width)])
+                    .areas(main_area);
+            ui::render_viewports(frame, &mut app, area);
+
+            if app.show_hud {
+                ui::render_hud(frame, &app, app.viewport.area);
+            }
+            if app.show_inspector {
+                ui::render_inspector(frame, &app, inspector_area);
+            }
 
-            let debug_info = Paragraph::new(format!(
-                "terminal size: {:?}\naspect ratio {:?}\ndrawmode: {:?}\nrendermode: {:?}\n\ncamera pos: {:?} \nyaw: {:.1}, \npitch: {:.1}",
-                app.terminal_size, app.camera.aspect_ratio, app.draw_mode, app.render_mode, app.camera.pos, app.camera.yaw, app.camera.pitch
-            ));
+            let console_line = if app.console.active {
+                Line::from(vec![
+                    Span::raw(":"),
+                    Span::raw(app.console.input.as_str()),
+                    Span::styled(" ", Style::default().bg(Color::White)),
+                ])
+            } else {
+                match &app.console.message {
+                    Some(ConsoleMessage::Info(message)) => Line::raw(message.as_str()),
+                    Some(ConsoleMessage::Error(message)) => {
+                        Line::styled(message.as_str(), Style::default().fg(Color::Red))
+                    }
+                    None => Line::default(),
+                }
+            };
 
-            frame.render_widget(canvas, area);
-            frame.render_widget(debug_info, area);
+            frame.render_widget(Paragraph::new(console_line), console_area);
         })?;
 
-        let event = app.get_event()?;
+        let event = app.get_event(app.frame_time.saturating_sub(frame_start.elapsed()))?;
         let action = app.process_event(event)?;
         app.process_action(action);
 
         if app.should_quit {
+            if graphics_shown {
+                clear_graphics(&mut terminal)?;
+            }
             return Ok(());
         }
     }
 }
+
+fn clear_graphics(terminal: &mut DefaultTerminal) -> Result<()> {
+    std::io::stdout().write_all(graphics::KITTY_DELETE_ALL.as_bytes())?;
+    terminal.clear()?;
+    Ok(())
+}
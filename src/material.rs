@@ -0,0 +1,269 @@
+use crate::color;
+use crate::texture::Texture;
+use crate::types::Vec3;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use ratatui::style::Color;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
+
+// How the surface of an object looks in every render mode
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub color: Color,
+    pub emissive: Color,          // added to the lit color, glows without light
+    pub specular: f64,            // strength of the highlight, 0 to 1
+    pub shininess: f64,           // the higher, the smaller the highlight
+    pub metal: f64,               // how much light is mirrored instead of scattered, 0 to 1
+    pub roughness: f64,           // how much the mirrored light is blurred, 0 to 1
+    pub wireframe: Option<Color>, // of the edges, the base color if not set
+    pub opacity: f64,             // 0 is invisible, 1 hides what is behind
+    pub texture: Option<Arc<Texture>>, // replaces the base color
+}
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            color: Color::Red,
+            emissive: Color::Black,
+            specular: 0.0,
+            shininess: 32.0,
+            metal: 0.0,
+            roughness: 0.0,
+            wireframe: None,
+            opacity: 1.0,
+            texture: None,
+        }
+    }
+}
+impl Material {
+    pub fn edge_color(&self) -> Color {
+        self.wireframe.unwrap_or(self.color)
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.opacity >= 1.0
+    }
+
+    // Lights a surface of the given base color. `normal` faces the viewer and
+    // `view` points from the surface to it, `lights` are the directions
+    // towards the lights with the strength of their red, green and blue that
+    // reaches the surface.
+    pub fn shade(
+        &self,
+        base: [u8; 3],
+        normal: Vec3,
+        view: Vec3,
+        lights: &[(Vec3, [f64; 3])],
+    ) -> [u8; 3] {
+        let mut diffuse = [0.0; 3];
+        let mut highlight = [0.0; 3];
+        for &(direction, strength) in lights {
+            let facing = normal.dot(direction);
+            if facing <= 0.0 {
+                continue;
+            }
+            let half = (direction + view).normalize();
+            let specular = self.specular * normal.dot(half).max(0.0).powf(self.shininess);
+            for c in 0..3 {
+                diffuse[c] += facing * strength[c];
+                highlight[c] += specular * strength[c];
+            }
+        }
+
+        let emissive = color::to_rgb(self.emissive, [0, 0, 0]);
+        [0, 1, 2].map(|c| {
+            let value = base[c] as f64 * (0.2 + 0.8 * diffuse[c])
+                + highlight[c] * 255.0
+                + emissive[c] as f64;
+            value.round().clamp(0.0, 255.0) as u8
+        })
+    }
+
+    pub fn load_library(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("could not read {}", path.display()))?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        Self::parse_library(&source, base_dir).wrap_err_with(|| format!("in {}", path.display()))
+    }
+
+    // Wavefront MTL files, with texture paths relative to `base_dir`. Only the
+    // properties that have a counterpart here are read.
+    pub fn parse_library(source: &str, base_dir: &Path) -> Result<Vec<Self>> {
+        let mut materials: Vec<Self> = Vec::new();
+
+        for (line_nr, line) in source.lines().enumerate() {
+            let mut parts = line.split_whitespace();
+            let Some(keyword) = parts.next() else {
+                continue;
+            };
+            let args: Vec<&str> = parts.collect();
+            let context = || format!("line {}", line_nr + 1);
+
+            if keyword == "newmtl" {
+                materials.push(Self {
+                    name: args.join(" "),
+                    ..Default::default()
+                });
+                continue;
+            }
+            let Some(material) = materials.last_mut() else {
+                if keyword.starts_with('#') {
+                    continue;
+                }
+                return Err(eyre!("'{}' before any newmtl", keyword)).wrap_err_with(context);
+            };
+            let number = |range: RangeInclusive<f64>| -> Result<f64> {
+                args.first()
+                    .and_then(|arg| arg.parse().ok())
+                    .filter(|number| range.contains(number))
+                    .ok_or_else(|| eyre!("{} must be a number in {:?}", keyword, range))
+            };
+
+            match keyword {
+                "Kd" => material.color = to_color(parse_rgb(&args).wrap_err_with(context)?),
+                "Ke" => material.emissive = to_color(parse_rgb(&args).wrap_err_with(context)?),
+                "Ks" => {
+                    let [r, g, b] = parse_rgb(&args).wrap_err_with(context)?;
+                    material.specular = r.max(g).max(b).clamp(0.0, 1.0);
+                }
+                "Ns" => material.shininess = number(0.0..=f64::MAX).wrap_err_with(context)?,
+                "d" => material.opacity = number(0.0..=1.0).wrap_err_with(context)?,
+                "Tr" => material.opacity = 1.0 - number(0.0..=1.0).wrap_err_with(context)?,
+                // From the PBR extension
+                "Pm" => material.metal = number(0.0..=1.0).wrap_err_with(context)?,
+                "Pr" => material.roughness = number(0.0..=1.0).wrap_err_with(context)?,
+                // Options come before the file name
+                "map_Kd" => {
+                    let Some(file) = args.last() else {
+                        return Err(eyre!("map_Kd needs a file")).wrap_err_with(context);
+                    };
+                    let texture = Texture::load(base_dir.join(file)).wrap_err_with(context)?;
+                    material.texture = Some(Arc::new(texture.with_mipmaps()));
+                }
+                _ => (), // Skip ambient colors, illumination models, other maps...
+            }
+        }
+
+        Ok(materials)
+    }
+
+    // The first material an OBJ file uses from its `mtllib` libraries, with
+    // the libraries relative to `base_dir`
+    pub fn from_obj(source: &str, base_dir: &Path) -> Result<Option<Self>> {
+        let mut libraries = Vec::new();
+        let mut used = None;
+        for line in source.lines() {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("mtllib") => libraries.extend(parts),
+                Some("usemtl") if used.is_none() => used = parts.next(),
+                _ => (),
+            }
+        }
+        let Some(used) = used else {
+            return Ok(None);
+        };
+
+        for library in libraries {
+            let materials = Self::load_library(base_dir.join(library))?;
+            if let Some(material) = materials.into_iter().find(|m| m.name == used) {
+                return Ok(Some(material));
+            }
+        }
+        Err(eyre!("material '{}' not found", used))
+    }
+}
+
+// Three numbers from 0 to 1, a single one is gray
+fn parse_rgb(args: &[&str]) -> Result<[f64; 3]> {
+    let values = args
+        .iter()
+        .map(|arg| arg.parse::<f64>().ok().filter(|value| value.is_finite()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| eyre!("invalid number"))?;
+    match *values.as_slice() {
+        [r, g, b] => Ok([r, g, b]),
+        [v] => Ok([v, v, v]),
+        _ => Err(eyre!("expected 3 numbers, got {}", values.len())),
+    }
+}
+
+fn to_color(rgb: [f64; 3]) -> Color {
+    let [r, g, b] = rgb.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Color::Rgb(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_material_library() {
+        let source = "# two materials\nnewmtl glass\nKa 0 0 0\nKd 0.2 0.4 1\nKs 0.5 0.5 0.5\nNs 64\nd 0.25\n\n\
+                      newmtl lamp\nKd 1 1 1\nKe 1 0.5 0\nillum 2\nPm 1\nPr 0.25\n";
+        let materials = Material::parse_library(source, Path::new(".")).unwrap();
+        let [glass, lamp] = materials.as_slice() else {
+            panic!("{:?}", materials);
+        };
+
+        assert_eq!(glass.name, "glass");
+        assert_eq!(glass.color, Color::Rgb(51, 102, 255));
+        assert_eq!((glass.specular, glass.shininess), (0.5, 64.0));
+        assert_eq!(glass.opacity, 0.25);
+        assert_eq!(lamp.emissive, Color::Rgb(255, 128, 0));
+        assert_eq!((lamp.metal, lamp.roughness), (1.0, 0.25));
+        assert!(Material::parse_library("Kd 1 1 1\n", Path::new(".")).is_err());
+        for line in [
+            "Ns nan",
+            "Ns inf",
+            "d NaN",
+            "Tr -inf",
+            "Kd 1 nan 1",
+            "Pm nan",
+            "Pr 2",
+        ] {
+            let source = format!("newmtl broken\n{}\n", line);
+            assert!(
+                Material::parse_library(&source, Path::new(".")).is_err(),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn shading_adds_highlight_and_glow() {
+        let plain = Material {
+            color: Color::Rgb(100, 0, 0),
+            ..Default::default()
+        };
+        let normal = Vec3::new(0.0, 0.0, -1.0);
+        let headlight = [(normal, [1.0; 3])];
+        assert_eq!(
+            plain.shade([100, 0, 0], normal, normal, &headlight),
+            [100, 0, 0]
+        );
+        // lights behind the surface leave only the ambient light
+        let behind = [(normal * -1.0, [1.0; 3])];
+        assert_eq!(
+            plain.shade([100, 0, 0], normal, normal, &behind),
+            [20, 0, 0]
+        );
+
+        let shiny = Material {
+            specular: 0.5,
+            emissive: Color::Rgb(0, 0, 10),
+            ..plain
+        };
+        assert_eq!(
+            shiny.shade([100, 0, 0], normal, normal, &headlight),
+            [228, 128, 138]
+        );
+        // the highlight is gone at a glancing angle, the glow stays
+        let glancing = [(Vec3::new(0.995, 0.0, -0.1).normalize(), [1.0; 3])];
+        assert_eq!(shiny.shade([100, 0, 0], normal, normal, &glancing)[2], 10);
+    }
+}
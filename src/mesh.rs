@@ -0,0 +1,351 @@
+use crate::material::Material;
+use crate::types::{MyShapes, Vec3};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    pub name: String,
+    pub vertices: Vec<Vec3>,
+    pub edges: Vec<(usize, usize)>,
+    pub faces: Vec<[usize; 3]>,
+    pub uvs: Vec<[f64; 2]>,
+    // Into `uvs` for the corners of each face, empty unless all faces have them
+    pub face_uvs: Vec<[usize; 3]>,
+    // The first one its OBJ file uses, objects start out with it
+    pub material: Option<Material>,
+    pub path: Option<PathBuf>, // of the OBJ file it was loaded from
+}
+impl Mesh {
+    pub fn cube() -> Self {
+        Self::builtin("cube").unwrap()
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let shapes = MyShapes::create_shapes();
+        let (vertices, edges, faces) = match name {
+            "cube" => (
+                shapes._cube_vertices.to_vec(),
+                shapes._cube_edges.to_vec(),
+                shapes._cube_faces.to_vec(),
+            ),
+            "pyramid" => (
+                shapes._pyramid_vertices.to_vec(),
+                shapes._pyramid_edges.to_vec(),
+                shapes._pyramid_faces.to_vec(),
+            ),
+            "prism" => (
+                shapes._prism_vertices.to_vec(),
+                shapes._prism_edges.to_vec(),
+                shapes._prism_faces.to_vec(),
+            ),
+            // A projection of a 4d shape, it has no meaningful solid faces
+            "tesseract" => (
+                shapes._tesseract_vertices.to_vec(),
+                shapes._tesseract_edges.to_vec(),
+                Vec::new(),
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            vertices,
+            edges,
+            faces,
+            uvs: Vec::new(),
+            face_uvs: Vec::new(),
+            material: None,
+            path: None,
+        })
+    }
+
+    // Whether this is still the builtin shape of its name, and not edited
+    pub fn is_builtin(&self) -> bool {
+        Self::builtin(&self.name).is_some_and(|builtin| {
+            builtin.vertices == self.vertices
+                && builtin.edges == self.edges
+                && builtin.faces == self.faces
+        })
+    }
+
+    pub fn load_obj(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("could not read {}", path.display()))?;
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("model"));
+
+        let mut mesh =
+            Self::parse_obj(name, &source).wrap_err_with(|| format!("in {}", path.display()))?;
+        mesh.material = Material::from_obj(&source, path.parent().unwrap_or(Path::new(".")))
+            .wrap_err_with(|| format!("in {}", path.display()))?;
+        // Scenes saved elsewhere still have to find it
+        mesh.path = Some(std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+        Ok(mesh)
+    }
+
+    pub fn parse_obj(name: String, source: &str) -> Result<Self> {
+        let mut mesh = Mesh {
+            name,
+            vertices: Vec::new(),
+            edges: Vec::new(),
+            faces: Vec::new(),
+            uvs: Vec::new(),
+            face_uvs: Vec::new(),
+            material: None,
+            path: None,
+        };
+        let mut seen_edges = HashSet::new();
+        let mut all_faces_have_uvs = true;
+
+        for (line_nr, line) in source.lines().enumerate() {
+            let mut parts = line.split_whitespace();
+            let Some(keyword) = parts.next() else {
+                continue;
+            };
+
+            match keyword {
+                "v" => {
+                    let coords = parts
+                        .take(3)
+                        .map(|p| p.parse::<f64>().ok().filter(|c| c.is_finite()))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| eyre!("invalid number"))
+                        .wrap_err_with(|| format!("line {}: invalid vertex", line_nr + 1))?;
+                    if coords.len() < 3 {
+                        return Err(eyre!("line {}: vertex needs 3 coordinates", line_nr + 1));
+                    }
+                    mesh.vertices
+                        .push(Vec3::new(coords[0], coords[1], coords[2]));
+                }
+                "vt" => {
+                    let coords = parts
+                        .take(2)
+                        .map(|p| p.parse::<f64>().ok().filter(|c| c.is_finite()))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| eyre!("invalid number"))
+                        .wrap_err_with(|| {
+                            format!("line {}: invalid texture coordinate", line_nr + 1)
+                        })?;
+                    let Some(&u) = coords.first() else {
+                        return Err(eyre!(
+                            "line {}: texture coordinate needs a u coordinate",
+                            line_nr + 1
+                        ));
+                    };
+                    mesh.uvs.push([u, coords.get(1).copied().unwrap_or(0.0)]);
+                }
+                // Faces and polylines both contribute their outline as edges,
+                // faces are additionally triangulated as a fan.
+                "f" | "l" => {
+                    let (indices, uvs): (Vec<_>, Vec<_>) = parts
+                        .map(|p| mesh.resolve_corner(p))
+                        .collect::<Result<Vec<_>>>()
+                        .wrap_err_with(|| format!("line {}", line_nr + 1))?
+                        .into_iter()
+                        .unzip();
+                    if indices.len() < 2 {
+                        continue;
+                    }
+
+                    for pair in indices.windows(2) {
+                        mesh.add_edge(&mut seen_edges, pair[0], pair[1]);
+                    }
+                    if keyword == "f" {
+                        mesh.add_edge(&mut seen_edges, indices[indices.len() - 1], indices[0]);
+                        for i in 1..indices.len().saturating_sub(1) {
+                            mesh.faces.push([indices[0], indices[i], indices[i + 1]]);
+                            match (uvs[0], uvs[i], uvs[i + 1]) {
+                                (Some(a), Some(b), Some(c)) => mesh.face_uvs.push([a, b, c]),
+                                _ => all_faces_have_uvs = false,
+                            }
+                        }
+                    }
+                }
+                _ => (), // Skip normals, groups, materials...
+            }
+        }
+        if !all_faces_have_uvs {
+            mesh.face_uvs.clear();
+        }
+
+        if mesh.vertices.is_empty() {
+            return Err(eyre!("model contains no vertices"));
+        }
+
+        Ok(mesh)
+    }
+
+    pub fn save_obj(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_obj())
+            .wrap_err_with(|| format!("could not write {}", path.display()))
+    }
+
+    // Triangles that were fanned out of one polygon are joined again, and the
+    // edges that aren't part of any face are written as polylines, so parsing
+    // it again gives back the same edges
+    pub fn to_obj(&self) -> String {
+        let mut source = format!("o {}\n", self.name);
+        for vertex in &self.vertices {
+            source += &format!("v {} {} {}\n", vertex.x, vertex.y, vertex.z);
+        }
+        let has_uvs = !self.face_uvs.is_empty();
+        if has_uvs {
+            for [u, v] in &self.uvs {
+                source += &format!("vt {} {}\n", u, v);
+            }
+        }
+
+        let edges: HashSet<(usize, usize)> = self
+            .edges
+            .iter()
+            .map(|&(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        // Corners are a vertex and, if there are any, a texture coordinate
+        let mut polygons: Vec<Vec<(usize, Option<usize>)>> = Vec::new();
+        for (i, &[a, b, c]) in self.faces.iter().enumerate() {
+            let uvs = self.face_uvs.get(i).map_or([None; 3], |uvs| uvs.map(Some));
+            let [a, b, c] = [(a, uvs[0]), (b, uvs[1]), (c, uvs[2])];
+            match polygons.last_mut() {
+                // The next triangle of a fan shares an edge that isn't drawn
+                Some(polygon)
+                    if polygon[0] == a
+                        && polygon.last() == Some(&b)
+                        && !edges.contains(&(a.0.min(b.0), a.0.max(b.0))) =>
+                {
+                    polygon.push(c)
+                }
+                _ => polygons.push(vec![a, b, c]),
+            }
+        }
+
+        let mut face_edges = HashSet::new();
+        for polygon in &polygons {
+            source.push('f');
+            for (n, &(vertex, uv)) in polygon.iter().enumerate() {
+                match uv {
+                    Some(uv) => source += &format!(" {}/{}", vertex + 1, uv + 1),
+                    None => source += &format!(" {}", vertex + 1),
+                }
+                let next = polygon[(n + 1) % polygon.len()].0;
+                face_edges.insert((vertex.min(next), vertex.max(next)));
+            }
+            source.push('\n');
+        }
+        for &(a, b) in &self.edges {
+            if !face_edges.contains(&(a.min(b), a.max(b))) {
+                source += &format!("l {} {}\n", a + 1, b + 1);
+            }
+        }
+        source
+    }
+
+    // A face corner is the vertex index optionally followed by the texture
+    // coordinate and normal indices, as in `v/vt/vn`, `v//vn` or `v/vt`
+    fn resolve_corner(&self, token: &str) -> Result<(usize, Option<usize>)> {
+        let mut parts = token.split('/');
+        let vertex = resolve_index(parts.next().unwrap_or_default(), self.vertices.len(), token)?;
+        let uv = match parts.next() {
+            Some(index) if !index.is_empty() => Some(resolve_index(index, self.uvs.len(), token)?),
+            _ => None,
+        };
+        Ok((vertex, uv))
+    }
+
+    fn add_edge(&mut self, seen: &mut HashSet<(usize, usize)>, a: usize, b: usize) {
+        let edge = (a.min(b), a.max(b));
+        if a != b && seen.insert(edge) {
+            self.edges.push(edge);
+        }
+    }
+}
+
+// OBJ indices are 1-based, negative indices count back from the last one
+fn resolve_index(index: &str, count: usize, token: &str) -> Result<usize> {
+    let index: i64 = index
+        .parse()
+        .wrap_err_with(|| format!("invalid index '{}'", token))?;
+
+    let count = count as i64;
+    let resolved = if index < 0 { count + index } else { index - 1 };
+
+    if resolved < 0 || resolved >= count {
+        return Err(eyre!("index {} out of range", index));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_faces_wind_outwards() {
+        for name in ["cube", "pyramid", "prism"] {
+            let mesh = Mesh::builtin(name).unwrap();
+            let center = mesh
+                .vertices
+                .iter()
+                .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &v| sum + v)
+                * (1.0 / mesh.vertices.len() as f64);
+            for &[a, b, c] in &mesh.faces {
+                let [a, b, c] = [a, b, c].map(|i| mesh.vertices[i]);
+                let normal = (b - a).cross(c - a);
+                assert!(normal.dot(a - center) > 0.0, "{} {:?}", name, [a, b, c]);
+            }
+        }
+    }
+
+    #[test]
+    fn obj_round_trips_builtin_shapes() {
+        for name in ["cube", "pyramid", "prism", "tesseract"] {
+            let mesh = Mesh::builtin(name).unwrap();
+            let parsed = Mesh::parse_obj(name.to_string(), &mesh.to_obj()).unwrap();
+
+            assert_eq!(parsed.vertices, mesh.vertices, "{}", name);
+            assert_eq!(parsed.faces, mesh.faces, "{}", name);
+            let sorted = |mesh: &Mesh| {
+                let mut edges = mesh.edges.clone();
+                edges
+                    .iter_mut()
+                    .for_each(|(a, b)| (*a, *b) = ((*a).min(*b), (*a).max(*b)));
+                edges.sort();
+                edges
+            };
+            assert_eq!(sorted(&parsed), sorted(&mesh), "{}", name);
+        }
+    }
+
+    #[test]
+    fn obj_texture_coordinates_round_trip() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                      vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                      f 1/1 2/2/1 3/3 4/-1\n";
+        let mesh = Mesh::parse_obj("quad".to_string(), source).unwrap();
+        assert_eq!(mesh.faces, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.face_uvs, vec![[0, 1, 2], [0, 2, 3]]);
+
+        let parsed = Mesh::parse_obj("quad".to_string(), &mesh.to_obj()).unwrap();
+        assert_eq!(parsed.uvs, mesh.uvs);
+        assert_eq!(parsed.faces, mesh.faces);
+        assert_eq!(parsed.face_uvs, mesh.face_uvs);
+
+        // Without coordinates for every face, none of them are textured
+        let partial = Mesh::parse_obj("quad".to_string(), &(source.to_string() + "f 1 3 4\n"));
+        assert!(partial.unwrap().face_uvs.is_empty());
+    }
+
+    #[test]
+    fn obj_rejects_non_finite_numbers() {
+        for source in ["v nan 0 0\n", "v 0 inf 0\n", "vt inf 0\n", "vt 0 nan\n"] {
+            let error = Mesh::parse_obj(String::from("broken"), source).unwrap_err();
+            assert_eq!(error.root_cause().to_string(), "invalid number");
+        }
+    }
+}
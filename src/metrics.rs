@@ -0,0 +1,127 @@
+// Terminal cells are taller than they are wide. Their real shape decides how
+// the rows and columns of an area map to a square image.
+use std::io::IsTerminal;
+
+// Height of a cell divided by its width, for when the terminal can't tell
+pub const DEFAULT_CELL_ASPECT: f64 = 2.25;
+
+// Pixel size of one cell, from the size of the window the terminal reports
+// along with its rows and columns, or by asking the terminal directly
+pub fn cell_pixel_size() -> Option<(usize, usize)> {
+    window_cell_size().or_else(query_cell_size)
+}
+
+// Height over width of a cell of the given pixel size
+pub fn cell_aspect(cell_size: Option<(usize, usize)>) -> f64 {
+    match cell_size {
+        Some((width, height)) => height as f64 / width as f64,
+        None => DEFAULT_CELL_ASPECT,
+    }
+}
+
+// TIOCGWINSZ, many terminals leave the pixel fields empty
+fn window_cell_size() -> Option<(usize, usize)> {
+    let size = ratatui::crossterm::terminal::window_size().ok()?;
+    if size.width == 0 || size.height == 0 || size.columns == 0 || size.rows == 0 {
+        return None;
+    }
+    Some((
+        (size.width / size.columns) as usize,
+        (size.height / size.rows) as usize,
+    ))
+}
+
+// `CSI 16 t` reports the cell size, `CSI 14 t` the size of the text area
+fn query_cell_size() -> Option<(usize, usize)> {
+    if let Some(size) = query("\x1b[16t").and_then(|reply| parse_size_report(&reply, 6)) {
+        return Some(size);
+    }
+
+    let (width, height) = query("\x1b[14t").and_then(|reply| parse_size_report(&reply, 4))?;
+    let (columns, rows) = ratatui::crossterm::terminal::size().ok()?;
+    if columns == 0 || rows == 0 {
+        return None;
+    }
+    Some((width / columns as usize, height / rows as usize))
+}
+
+// Parses `CSI <code> ; <height> ; <width> t` into width and height
+pub fn parse_size_report(reply: &str, code: usize) -> Option<(usize, usize)> {
+    let body = reply.strip_prefix("\x1b[")?.strip_suffix('t')?;
+    let mut fields = body.split(';').map(|field| field.parse::<usize>().ok());
+    if fields.next()?? != code {
+        return None;
+    }
+    let height = fields.next()??;
+    let width = fields.next()??;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+// Writes a request and reads the reply up to its final `t`. The input is read
+// straight from the file descriptor so nothing the event reader needs gets
+// buffered here, but keys pressed while waiting for the reply are lost.
+#[cfg(unix)]
+fn query(request: &str) -> Option<String> {
+    use ratatui::crossterm::terminal;
+    use std::io::Write;
+
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return None;
+    }
+
+    // The reply must not be echoed or wait for a newline
+    let was_raw = terminal::is_raw_mode_enabled().ok()?;
+    if !was_raw {
+        terminal::enable_raw_mode().ok()?;
+    }
+
+    let mut stdout = std::io::stdout();
+    let mut reply = Vec::new();
+    if stdout.write_all(request.as_bytes()).is_ok() && stdout.flush().is_ok() {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(100);
+        while reply.last() != Some(&b't') && reply.len() < 64 {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let mut poll = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: a single valid pollfd, and a one byte buffer for read
+            if unsafe { libc::poll(&mut poll, 1, remaining.as_millis() as libc::c_int) } <= 0 {
+                break;
+            }
+            let mut byte = 0u8;
+            if unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } != 1 {
+                break;
+            }
+            reply.push(byte);
+        }
+    }
+
+    if !was_raw {
+        let _ = terminal::disable_raw_mode();
+    }
+    String::from_utf8(reply).ok()
+}
+
+#[cfg(not(unix))]
+fn query(_request: &str) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_size_reports() {
+        assert_eq!(parse_size_report("\x1b[6;20;10t", 6), Some((10, 20)));
+        assert_eq!(parse_size_report("\x1b[4;480;800t", 4), Some((800, 480)));
+        assert_eq!(parse_size_report("\x1b[4;480;800t", 6), None);
+        assert_eq!(parse_size_report("\x1b[6;0;0t", 6), None);
+        assert_eq!(parse_size_report("\x1b[?62;4c", 6), None);
+    }
+}
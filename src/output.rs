@@ -0,0 +1,285 @@
+use crate::framebuffer::Framebuffer;
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Color, symbols::Marker};
+
+// Maps a framebuffer to terminal cells. The framebuffer handed to `render` is
+// `cell_size` times the size of the area.
+pub trait OutputBackend {
+    // Pixels per cell horizontally and vertically
+    fn cell_size(&self) -> (usize, usize);
+
+    fn render(&self, framebuffer: &Framebuffer, area: Rect, buf: &mut Buffer, background: Color);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawMode {
+    Braille,
+    Dot,
+    HalfBlock,
+    Block,
+    Bar,
+    Ascii,
+    Pixels,
+    // A bitmap through a terminal graphics protocol, see `GraphicsBackend`
+    Graphics,
+}
+impl DrawMode {
+    pub fn backend(self) -> &'static dyn OutputBackend {
+        match self {
+            DrawMode::Braille => &BrailleBackend,
+            DrawMode::Dot => &GlyphBackend { symbol: "•" },
+            DrawMode::HalfBlock => &HalfBlockBackend,
+            DrawMode::Block => &GlyphBackend { symbol: "█" },
+            DrawMode::Bar => &GlyphBackend { symbol: "▄" },
+            DrawMode::Ascii => &AsciiBackend,
+            DrawMode::Pixels => &PixelBackend,
+            // Needs the pixel size of the cells, so it has to be passed as a
+            // custom backend and falls back to braille otherwise
+            DrawMode::Graphics => &BrailleBackend,
+        }
+    }
+}
+impl From<Marker> for DrawMode {
+    fn from(marker: Marker) -> Self {
+        match marker {
+            Marker::Braille => DrawMode::Braille,
+            Marker::Dot => DrawMode::Dot,
+            Marker::HalfBlock => DrawMode::HalfBlock,
+            Marker::Block => DrawMode::Block,
+            Marker::Bar => DrawMode::Bar,
+        }
+    }
+}
+
+// The pixels of one cell, row by row
+struct CellPixels<'a> {
+    framebuffer: &'a Framebuffer,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+impl CellPixels<'_> {
+    fn index(&self, dx: usize, dy: usize) -> usize {
+        self.framebuffer.index(self.x + dx, self.y + dy)
+    }
+
+    fn is_covered(&self, dx: usize, dy: usize) -> bool {
+        self.framebuffer.coverage[self.index(dx, dy)] > 0.0
+    }
+
+    fn coverage(&self) -> f32 {
+        let mut sum = 0.0;
+        for dy in 0..self.height {
+            for dx in 0..self.width {
+                sum += self.framebuffer.coverage[self.index(dx, dy)];
+            }
+        }
+        sum / (self.width * self.height) as f32
+    }
+
+    // The color of the covered pixel closest to the camera
+    fn nearest_color(&self) -> Option<[u8; 3]> {
+        let mut nearest: Option<(f64, [u8; 3])> = None;
+        for dy in 0..self.height {
+            for dx in 0..self.width {
+                let i = self.index(dx, dy);
+                if self.framebuffer.coverage[i] <= 0.0 {
+                    continue;
+                }
+                let depth = self.framebuffer.depth[i];
+                if nearest.is_none_or(|(nearest_depth, _)| depth < nearest_depth) {
+                    nearest = Some((depth, self.framebuffer.color[i]));
+                }
+            }
+        }
+        nearest.map(|(_, color)| color)
+    }
+}
+
+// Calls `cell` for every cell in the area with its pixels, and writes the
+// returned symbol and foreground color. `None` leaves the cell empty.
+fn map_cells(
+    framebuffer: &Framebuffer,
+    (width, height): (usize, usize),
+    area: Rect,
+    buf: &mut Buffer,
+    background: Color,
+    cell: impl Fn(&CellPixels) -> Option<(char, [u8; 3])>,
+) {
+    for row in 0..area.height {
+        for col in 0..area.width {
+            let pixels = CellPixels {
+                framebuffer,
+                x: col as usize * width,
+                y: row as usize * height,
+                width,
+                height,
+            };
+            if pixels.x + width > framebuffer.width || pixels.y + height > framebuffer.height {
+                continue;
+            }
+
+            let target = &mut buf[(area.x + col, area.y + row)];
+            target.reset();
+            target.set_bg(background);
+            match cell(&pixels) {
+                Some((symbol, [r, g, b])) => {
+                    target.set_char(symbol).set_fg(Color::Rgb(r, g, b));
+                }
+                None => {
+                    target.set_char(' ');
+                }
+            }
+        }
+    }
+}
+
+// 2x4 dots per cell using the unicode braille patterns
+pub struct BrailleBackend;
+impl OutputBackend for BrailleBackend {
+    fn cell_size(&self) -> (usize, usize) {
+        (2, 4)
+    }
+
+    fn render(&self, framebuffer: &Framebuffer, area: Rect, buf: &mut Buffer, background: Color) {
+        // Bit of each dot in the braille pattern, indexed by [row][column]
+        const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+        map_cells(
+            framebuffer,
+            self.cell_size(),
+            area,
+            buf,
+            background,
+            |pixels| {
+                let mut pattern = 0;
+                for (dy, row) in DOTS.iter().enumerate() {
+                    for (dx, bit) in row.iter().enumerate() {
+                        if pixels.is_covered(dx, dy) {
+                            pattern |= bit;
+                        }
+                    }
+                }
+                let symbol = char::from_u32(0x2800 + pattern)?;
+                pixels.nearest_color().map(|color| (symbol, color))
+            },
+        );
+    }
+}
+
+// Upper and lower half blocks, doubling the vertical resolution
+pub struct HalfBlockBackend;
+impl OutputBackend for HalfBlockBackend {
+    fn cell_size(&self) -> (usize, usize) {
+        (1, 2)
+    }
+
+    fn render(&self, framebuffer: &Framebuffer, area: Rect, buf: &mut Buffer, background: Color) {
+        map_cells(
+            framebuffer,
+            self.cell_size(),
+            area,
+            buf,
+            background,
+            |pixels| {
+                let symbol = match (pixels.is_covered(0, 0), pixels.is_covered(0, 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => return None,
+                };
+                pixels.nearest_color().map(|color| (symbol, color))
+            },
+        );
+    }
+}
+
+// A single symbol for every cell that contains something
+pub struct GlyphBackend {
+    pub symbol: &'static str,
+}
+impl OutputBackend for GlyphBackend {
+    fn cell_size(&self) -> (usize, usize) {
+        (1, 1)
+    }
+
+    fn render(&self, framebuffer: &Framebuffer, area: Rect, buf: &mut Buffer, background: Color) {
+        let symbol = self.symbol.chars().next().unwrap_or('█');
+        map_cells(
+            framebuffer,
+            self.cell_size(),
+            area,
+            buf,
+            background,
+            |pixels| pixels.nearest_color().map(|color| (symbol, color)),
+        );
+    }
+}
+
+// Picks a character from a ramp by how much of the cell is covered
+pub struct AsciiBackend;
+impl AsciiBackend {
+    pub const RAMP: &'static [u8] = b" .:-=+*#%@";
+}
+impl OutputBackend for AsciiBackend {
+    fn cell_size(&self) -> (usize, usize) {
+        (2, 4)
+    }
+
+    fn render(&self, framebuffer: &Framebuffer, area: Rect, buf: &mut Buffer, background: Color) {
+        map_cells(
+            framebuffer,
+            self.cell_size(),
+            area,
+            buf,
+            background,
+            |pixels| {
+                let color = pixels.nearest_color()?;
+                let steps = (Self::RAMP.len() - 1) as f32;
+                // Anything covered gets at least the first visible character
+                let level = (pixels.coverage() * steps).ceil().clamp(1.0, steps) as usize;
+                Some((Self::RAMP[level] as char, color))
+            },
+        );
+    }
+}
+
+// Two pixels per cell with their own color each, the upper half block in the
+// color of the top pixel over a background in the color of the bottom one
+pub struct PixelBackend;
+impl OutputBackend for PixelBackend {
+    fn cell_size(&self) -> (usize, usize) {
+        (1, 2)
+    }
+
+    fn render(&self, framebuffer: &Framebuffer, area: Rect, buf: &mut Buffer, background: Color) {
+        // Uncovered pixels keep the background of the widget, so a terminal
+        // default background stays transparent
+        let pixel_color = |x: usize, y: usize| {
+            let i = framebuffer.index(x, y);
+            if framebuffer.coverage[i] > 0.0 {
+                let [r, g, b] = framebuffer.color[i];
+                Color::Rgb(r, g, b)
+            } else {
+                background
+            }
+        };
+
+        for row in 0..area.height {
+            for col in 0..area.width {
+                let (x, y) = (col as usize, row as usize * 2);
+                if x >= framebuffer.width || y + 1 >= framebuffer.height {
+                    continue;
+                }
+
+                let target = &mut buf[(area.x + col, area.y + row)];
+                target.reset();
+                target
+                    .set_char('▀')
+                    .set_fg(pixel_color(x, y))
+                    .set_bg(pixel_color(x, y + 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,1815 @@
+use crate::camera::{ray_hits_aabb, Camera};
+use crate::color;
+use crate::framebuffer::Framebuffer;
+use crate::material::Material;
+use crate::scene::{Light, Object, Scene};
+use crate::sdf;
+use crate::types::Vec3;
+
+use ratatui::style::Color;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+// A projected line in normalized device coordinates, x and y in [-1, 1],
+// with the view space depth of both ends
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    pub x1: f64,
+    pub y1: f64,
+    pub z1: f64,
+    pub x2: f64,
+    pub y2: f64,
+    pub z2: f64,
+    pub color: Color,
+    pub opacity: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn clip_line_to_viewport(
+    x1: f64,
+    y1: f64,
+    x2: f64,
+    y2: f64,
+    xmin: f64,
+    xmax: f64,
+    ymin: f64,
+    ymax: f64,
+) -> Option<(f64, f64, f64, f64)> {
+    let dx = x2 - x1;
+    let dy = y2 - y1;
+
+    let mut t0 = 0.0;
+    let mut t1 = 1.0;
+
+    let clip = |p: f64, q: f64, t0: &mut f64, t1: &mut f64| -> bool {
+        if p == 0.0 {
+            return q >= 0.0;
+        }
+        let r = q / p;
+        if p < 0.0 {
+            if r > *t1 {
+                return false;
+            }
+            if r > *t0 {
+                *t0 = r;
+            }
+        } else {
+            if r < *t0 {
+                return false;
+            }
+            if r < *t1 {
+                *t1 = r;
+            }
+        }
+        true
+    };
+
+    if clip(-dx, x1 - xmin, &mut t0, &mut t1)
+        && clip(dx, xmax - x1, &mut t0, &mut t1)
+        && clip(-dy, y1 - ymin, &mut t0, &mut t1)
+        && clip(dy, ymax - y1, &mut t0, &mut t1)
+    {
+        let nx1 = x1 + t0 * dx;
+        let ny1 = y1 + t0 * dy;
+        let nx2 = x1 + t1 * dx;
+        let ny2 = y1 + t1 * dy;
+        Some((nx1, ny1, nx2, ny2))
+    } else {
+        None
+    }
+}
+
+// Edges are drawn in the wireframe color of their material, those of the
+// selected object are highlighted
+pub fn project_edges(scene: &Scene, camera: &Camera, selected: Option<usize>) -> Vec<Segment> {
+    let mut segments = Vec::new();
+
+    for (index, object) in scene.objects.iter().enumerate() {
+        let color = edge_color(object, selected == Some(index));
+        segments.extend(
+            project_object_edges(object, camera, color)
+                .into_iter()
+                .map(|segment| with_opacity(segment, object)),
+        );
+    }
+
+    segments
+}
+
+fn edge_color(object: &Object, selected: bool) -> Color {
+    if selected {
+        SELECTED_COLOR
+    } else {
+        object.material.edge_color()
+    }
+}
+
+fn with_opacity(segment: Segment, object: &Object) -> Segment {
+    Segment {
+        opacity: object.material.opacity as f32,
+        ..segment
+    }
+}
+
+pub const SELECTED_COLOR: Color = Color::Yellow;
+
+// How the edges behind faces are drawn in wireframes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HiddenLines {
+    Show,
+    Hide,
+    Dashed, // and dimmed
+}
+
+// Whether the lights of a scene cast shadows in the solid and raycast modes.
+// Soft shadows sample this many points across each light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shadows {
+    Off,
+    Hard,
+    Soft(usize),
+}
+
+// Samples per light of soft shadows, unless given
+pub const SOFT_SHADOW_SAMPLES: usize = 16;
+
+// A wireframe without the edges hidden behind faces
+#[derive(Debug, Clone, Default)]
+pub struct Wireframe {
+    pub segments: Vec<Segment>,
+    pub culled: usize, // faces turned away from the camera
+    pub hidden: usize, // pieces of edges behind faces
+}
+
+// Edges whose faces all face away from the camera are hidden right away, the
+// others are cut into pieces that are hidden when a ray from the camera hits
+// a face in front of them. Only faces turned towards the camera can cover
+// anything, the others are culled before, and so are transparent objects.
+pub fn project_hidden_edges(
+    scene: &Scene,
+    camera: &Camera,
+    selected: Option<usize>,
+    hidden_lines: HiddenLines,
+) -> Wireframe {
+    let mut wireframe = Wireframe::default();
+    let mut occluders = world_triangles(scene);
+    occluders.retain(|object| scene.objects[object.object].material.is_opaque());
+    for object in &mut occluders {
+        let faces = object.triangles.len();
+        object
+            .triangles
+            .retain(|&triangle| !is_back_face(camera, triangle));
+        wireframe.culled += faces - object.triangles.len();
+    }
+
+    for (index, object) in scene.objects.iter().enumerate() {
+        let color = edge_color(object, selected == Some(index));
+        let vertices = object.world_vertices();
+
+        // Whether any face along an edge faces the camera
+        let mut front = HashMap::new();
+        for &[a, b, c] in &object.mesh.faces {
+            let facing = !is_back_face(camera, [vertices[a], vertices[b], vertices[c]]);
+            for (a, b) in [(a, b), (b, c), (c, a)] {
+                *front.entry((a.min(b), a.max(b))).or_insert(false) |= facing;
+            }
+        }
+
+        for &(a, b) in &object.mesh.edges {
+            let (start, end) = (vertices[a], vertices[b]);
+            let projected = project_line(camera, start, end, color);
+            if projected.is_empty() {
+                continue;
+            }
+
+            let runs = if front.get(&(a.min(b), a.max(b))) == Some(&false) {
+                vec![(0.0, 1.0, false)]
+            } else {
+                let length: f64 = projected
+                    .iter()
+                    .map(|segment| (segment.x2 - segment.x1).hypot(segment.y2 - segment.y1))
+                    .sum();
+                let pieces = (length * 50.0).ceil().clamp(1.0, 200.0) as usize;
+                visible_runs(&occluders, camera, start, end, pieces)
+            };
+
+            for (t0, t1, visible) in runs {
+                let (p0, p1) = (start + (end - start) * t0, start + (end - start) * t1);
+                if visible {
+                    wireframe.segments.extend(
+                        project_line(camera, p0, p1, color)
+                            .into_iter()
+                            .map(|segment| with_opacity(segment, object)),
+                    );
+                    continue;
+                }
+                wireframe.hidden += 1;
+                if hidden_lines == HiddenLines::Dashed {
+                    for segment in project_line(camera, p0, p1, dim(color)) {
+                        wireframe
+                            .segments
+                            .extend(dashes(with_opacity(segment, object)));
+                    }
+                }
+            }
+        }
+    }
+
+    wireframe
+}
+
+// Faces are wound so that (b - a) x (c - a) points out of the mesh
+pub fn is_back_face(camera: &Camera, [a, b, c]: [Vec3; 3]) -> bool {
+    (b - a).cross(c - a).dot(a - camera.pos) >= 0.0
+}
+
+// Splits a line into pieces and joins the neighbouring ones that are both
+// visible or both hidden, as (start, end, visible) along the line
+fn visible_runs(
+    occluders: &[WorldTriangles],
+    camera: &Camera,
+    start: Vec3,
+    end: Vec3,
+    pieces: usize,
+) -> Vec<(f64, f64, bool)> {
+    let mut runs: Vec<(f64, f64, bool)> = Vec::new();
+    for piece in 0..pieces {
+        let (t0, t1) = (
+            piece as f64 / pieces as f64,
+            (piece + 1) as f64 / pieces as f64,
+        );
+        let point = start + (end - start) * ((t0 + t1) / 2.0);
+        let (origin, ray) = camera.ray_to(point);
+        let distance = (point - origin).length();
+        // Faces along the edge itself are hit at about the same distance
+        let visible = distance == 0.0
+            || nearest_hit(occluders, origin, ray)
+                .is_none_or(|hit| hit.t >= distance * (1.0 - 1e-6) - 1e-9);
+
+        match runs.last_mut() {
+            Some(run) if run.2 == visible => run.1 = t1,
+            _ => runs.push((t0, t1, visible)),
+        }
+    }
+    runs
+}
+
+fn dim(color: Color) -> Color {
+    let [r, g, b] = color::to_rgb(color, [255, 255, 255]).map(|c| (c as f64 * 0.4) as u8);
+    Color::Rgb(r, g, b)
+}
+
+// Every other piece of a segment, in pieces of about the same length on screen
+fn dashes(segment: Segment) -> Vec<Segment> {
+    const DASH: f64 = 0.03; // in normalized device coordinates
+
+    let length = (segment.x2 - segment.x1).hypot(segment.y2 - segment.y1);
+    let pieces = ((length / DASH).round() as usize).max(1) | 1; // starts and ends with a dash
+    let point = |t: f64| {
+        // the inverse of the depth is linear in screen space
+        let z = 1.0 / (1.0 / segment.z1 + (1.0 / segment.z2 - 1.0 / segment.z1) * t);
+        (
+            segment.x1 + (segment.x2 - segment.x1) * t,
+            segment.y1 + (segment.y2 - segment.y1) * t,
+            z,
+        )
+    };
+    (0..pieces)
+        .step_by(2)
+        .map(|piece| {
+            let (x1, y1, z1) = point(piece as f64 / pieces as f64);
+            let (x2, y2, z2) = point((piece + 1) as f64 / pieces as f64);
+            Segment {
+                x1,
+                y1,
+                z1,
+                x2,
+                y2,
+                z2,
+                ..segment
+            }
+        })
+        .collect()
+}
+
+pub fn project_object_edges(object: &Object, camera: &Camera, color: Color) -> Vec<Segment> {
+    let vertices = object.world_vertices();
+    object
+        .mesh
+        .edges
+        .iter()
+        .flat_map(|&(start, end)| project_line(camera, vertices[start], vertices[end], color))
+        .collect()
+}
+
+// Projects a line between two points in world space, into nothing when no part
+// of it is in front of the camera and on screen. Panoramic projections bend it
+// into several segments.
+pub fn project_line(camera: &Camera, start: Vec3, end: Vec3, color: Color) -> Vec<Segment> {
+    let start = camera.apply_view_transform(start - camera.pos);
+    let end = camera.apply_view_transform(end - camera.pos);
+
+    if camera.is_panoramic() {
+        let mut segments = Vec::new();
+        project_curve(camera, start, end, color, 0, &mut segments);
+        return segments;
+    }
+
+    // Cut off the part of the line behind the camera before projecting
+    let Some((start, end)) = camera.clip_line_to_near_plane(start, end) else {
+        return Vec::new();
+    };
+    project_view_line(camera, start, end, color)
+        .into_iter()
+        .collect()
+}
+
+// Splits a line in view space in the middle until the middle lands where a
+// straight segment between the projected ends would put it. Pieces that still
+// bend at the smallest size jump across the seam of the projection, or pass
+// through the camera, and are left out.
+fn project_curve(
+    camera: &Camera,
+    start: Vec3,
+    end: Vec3,
+    color: Color,
+    level: usize,
+    segments: &mut Vec<Segment>,
+) {
+    const MIN_LEVEL: usize = 2; // curves bending both ways can look straight at first
+    const MAX_LEVEL: usize = 10;
+    const TOLERANCE: f64 = 0.004; // in normalized device coordinates
+
+    let middle = (start + end) * 0.5;
+    let (x1, y1) = camera.project_view_point(start);
+    let (x2, y2) = camera.project_view_point(end);
+    let (xm, ym) = camera.project_view_point(middle);
+    let straight = ((x1 + x2) / 2.0 - xm).hypot((y1 + y2) / 2.0 - ym) <= TOLERANCE;
+
+    if level < MIN_LEVEL || (!straight && level < MAX_LEVEL) {
+        project_curve(camera, start, middle, color, level + 1, segments);
+        project_curve(camera, middle, end, color, level + 1, segments);
+        return;
+    }
+    if straight && camera.depth(start).min(camera.depth(end)) > camera.near_plane {
+        segments.extend(project_view_line(camera, start, end, color));
+    }
+}
+
+// Projects a straight segment between two points in view space in front of
+// the camera
+fn project_view_line(camera: &Camera, start: Vec3, end: Vec3, color: Color) -> Option<Segment> {
+    let (x1, y1) = camera.project_view_point(start);
+    let (x2, y2) = camera.project_view_point(end);
+
+    let (cx1, cy1, cx2, cy2) = clip_line_to_viewport(x1, y1, x2, y2, -1.0, 1.0, -1.0, 1.0)?;
+
+    // The inverse of the depth is linear in screen space, unless the
+    // projection is parallel. Panoramic pieces are short enough for either.
+    let (z1, z2) = (camera.depth(start), camera.depth(end));
+    let depth_at = |x: f64, y: f64| {
+        let t = if (x2 - x1).abs() > (y2 - y1).abs() {
+            (x - x1) / (x2 - x1)
+        } else if y2 != y1 {
+            (y - y1) / (y2 - y1)
+        } else {
+            0.0
+        };
+        if camera.is_parallel() || camera.is_panoramic() {
+            z1 + (z2 - z1) * t
+        } else {
+            1.0 / (1.0 / z1 + (1.0 / z2 - 1.0 / z1) * t)
+        }
+    };
+
+    Some(Segment {
+        x1: cx1,
+        y1: cy1,
+        z1: depth_at(cx1, cy1),
+        x2: cx2,
+        y2: cy2,
+        z2: depth_at(cx2, cy2),
+        color,
+        opacity: 1.0,
+    })
+}
+
+// Transparent faces a ray passes through before it gives up
+const MAX_LAYERS: usize = 16;
+
+// Casts a ray through the center of every pixel and shades the closest face it
+// hits with its material, lit by the lights of the scene that no other face
+// is in the way of, or by a light at the camera. Rays go on through
+// transparent faces and mix the colors of what they pass. Textured faces take
+// their color from where the ray hits them, which is perspective correct by
+// definition.
+pub fn raycast(scene: &Scene, camera: &Camera, shadows: Shadows, framebuffer: &mut Framebuffer) {
+    let objects = world_triangles(scene);
+    let (pixel_width, pixel_height) = (
+        2.0 / framebuffer.width as f64,
+        2.0 / framebuffer.height as f64,
+    );
+
+    for py in 0..framebuffer.height {
+        for px in 0..framebuffer.width {
+            let u = (px as f64 + 0.5) * pixel_width - 1.0;
+            let v = 1.0 - (py as f64 + 0.5) * pixel_height;
+            let Some((origin, ray)) = camera.ray(u, v) else {
+                continue;
+            };
+            let neighbours = [
+                camera.ray(u + pixel_width, v),
+                camera.ray(u, v - pixel_height),
+            ];
+
+            let mut color = [0.0; 3];
+            let mut transmittance = 1.0;
+            let mut first_depth = None;
+            let mut start = 0.0;
+            for _ in 0..MAX_LAYERS {
+                let Some(mut hit) = nearest_hit(&objects, origin + ray * start, ray) else {
+                    break;
+                };
+                hit.t += start;
+                let depth =
+                    camera.depth(camera.apply_view_transform(origin + ray * hit.t - camera.pos));
+                if depth < camera.near_plane {
+                    break;
+                }
+                first_depth.get_or_insert(depth);
+
+                let material = &scene.objects[hit.object].material;
+                let base = texture_color(scene, &objects, &hit, neighbours)
+                    .unwrap_or_else(|| color::to_rgb(material.color, [255, 0, 0]));
+                // Faces are lit from both sides
+                let normal = if hit.normal.dot(ray) > 0.0 {
+                    hit.normal * -1.0
+                } else {
+                    hit.normal
+                };
+                let view = ray * -1.0;
+                let lights: Vec<(Vec3, [f64; 3])> = if scene.lights.is_empty() {
+                    vec![(view, [1.0; 3])]
+                } else {
+                    let point = origin + ray * hit.t;
+                    let noise = interleaved_noise(px, py);
+                    scene
+                        .lights
+                        .iter()
+                        .map(|light| {
+                            light_at(&objects, scene, light, point, normal, shadows, noise)
+                        })
+                        .collect()
+                };
+                let shaded = material.shade(base, normal, view, &lights);
+                for c in 0..3 {
+                    color[c] += shaded[c] as f64 * material.opacity * transmittance;
+                }
+                transmittance *= 1.0 - material.opacity;
+                if transmittance < 0.01 {
+                    break;
+                }
+                start = hit.t + 1e-6;
+            }
+
+            let coverage = 1.0 - transmittance;
+            if let Some(depth) = first_depth.filter(|_| coverage > 0.0) {
+                let color = color.map(|c| (c / coverage).round().clamp(0.0, 255.0) as u8);
+                framebuffer.plot(px as i64, py as i64, depth, color, coverage as f32);
+            }
+        }
+    }
+}
+
+// Fills the faces of the objects pixel by pixel into the depth buffer and
+// shades them like the raycast mode, but with the shadows looked up in a
+// shadow map of every light instead of casting rays. Transparent objects are
+// blended over the opaque ones, the furthest first, and don't cast shadows.
+// Faces bend under the panoramic projections, so those are raycast instead.
+pub fn rasterize(
+    scene: &Scene,
+    camera: &Camera,
+    shadows: Shadows,
+    shadow_maps: &mut ShadowMaps,
+    framebuffer: &mut Framebuffer,
+) {
+    if camera.is_panoramic() {
+        raycast(scene, camera, shadows, framebuffer);
+        return;
+    }
+
+    let objects = world_triangles(scene);
+    let shadow_maps: &[ShadowMap] = match shadows {
+        Shadows::Off => &[],
+        _ => shadow_maps.prepare(scene, &objects),
+    };
+    let (pixel_width, pixel_height) = (
+        2.0 / framebuffer.width as f64,
+        2.0 / framebuffer.height as f64,
+    );
+
+    let mut order: Vec<&WorldTriangles> = objects.iter().collect();
+    let is_opaque = |object: &WorldTriangles| scene.objects[object.object].material.is_opaque();
+    let distance =
+        |object: &WorldTriangles| ((object.min + object.max) * 0.5 - camera.pos).length();
+    order.sort_by(|a, b| {
+        is_opaque(b)
+            .cmp(&is_opaque(a))
+            .then(distance(b).total_cmp(&distance(a)))
+    });
+
+    for object in order {
+        let material = &scene.objects[object.object].material;
+        for (face, &triangle) in object.triangles.iter().enumerate() {
+            let [a, b, c] = triangle;
+            let face_normal = (b - a).cross(c - a).normalize();
+            let (width, height) = (framebuffer.width, framebuffer.height);
+            rasterize_triangle(
+                camera,
+                triangle,
+                width,
+                height,
+                |px, py, depth, barycentric| {
+                    if depth > framebuffer.depth[framebuffer.index(px, py)] {
+                        return;
+                    }
+                    let point = a * (1.0 - barycentric.0 - barycentric.1)
+                        + b * barycentric.0
+                        + c * barycentric.1;
+                    let (_, ray) = camera.ray_to(point);
+                    let u = (px as f64 + 0.5) * pixel_width - 1.0;
+                    let v = 1.0 - (py as f64 + 0.5) * pixel_height;
+                    let neighbours = [
+                        camera.ray(u + pixel_width, v),
+                        camera.ray(u, v - pixel_height),
+                    ];
+                    let hit = Hit {
+                        object: object.object,
+                        t: depth,
+                        normal: face_normal,
+                        face,
+                        barycentric,
+                    };
+                    let base = texture_color(scene, &objects, &hit, neighbours)
+                        .unwrap_or_else(|| color::to_rgb(material.color, [255, 0, 0]));
+                    // Faces are lit from both sides
+                    let normal = if face_normal.dot(ray) > 0.0 {
+                        face_normal * -1.0
+                    } else {
+                        face_normal
+                    };
+                    let view = ray * -1.0;
+                    let lights: Vec<(Vec3, [f64; 3])> = if scene.lights.is_empty() {
+                        vec![(view, [1.0; 3])]
+                    } else {
+                        let noise = interleaved_noise(px, py);
+                        scene
+                            .lights
+                            .iter()
+                            .enumerate()
+                            .map(|(index, light)| {
+                                let direction = (light.position - point).normalize();
+                                let strength = light_strength(light);
+                                let visible = match shadow_maps.get(index) {
+                                    Some(map) if normal.dot(direction) > 0.0 => {
+                                        map.visibility(light, point, normal, shadows, noise)
+                                    }
+                                    _ => 1.0,
+                                };
+                                (direction, strength.map(|channel| channel * visible))
+                            })
+                            .collect()
+                    };
+                    let color = material.shade(base, normal, view, &lights);
+                    framebuffer.plot(px as i64, py as i64, depth, color, material.opacity as f32);
+                },
+            );
+        }
+    }
+}
+
+// Calls `plot` with the pixel, the depth and the weights of the second and
+// third corner at every pixel center the triangle covers, after clipping it
+// to the near plane of the camera. Under perspective the weights are
+// interpolated by the inverse depth, so they are correct across the face.
+fn rasterize_triangle(
+    camera: &Camera,
+    triangle: [Vec3; 3],
+    width: usize,
+    height: usize,
+    mut plot: impl FnMut(usize, usize, f64, (f64, f64)),
+) {
+    let corners: Vec<(Vec3, (f64, f64))> = triangle
+        .into_iter()
+        .zip([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)])
+        .map(|(corner, weights)| (camera.apply_view_transform(corner - camera.pos), weights))
+        .collect();
+    let polygon = clip_polygon_to_near_plane(camera.near_plane, &corners);
+    if polygon.len() < 3 {
+        return;
+    }
+
+    // In pixels, with the pixel centers at the halves
+    let screen: Vec<(f64, f64)> = polygon
+        .iter()
+        .map(|&(view, _)| {
+            let (x, y) = camera.project_view_point(view);
+            (
+                (x + 1.0) / 2.0 * width as f64,
+                (1.0 - y) / 2.0 * height as f64,
+            )
+        })
+        .collect();
+    let edge = |(ax, ay): (f64, f64), (bx, by): (f64, f64), (px, py): (f64, f64)| {
+        (bx - ax) * (py - ay) - (by - ay) * (px - ax)
+    };
+
+    for second in 1..polygon.len() - 1 {
+        let fan = [0, second, second + 1];
+        let [p0, p1, p2] = fan.map(|corner| screen[corner]);
+        let area = edge(p0, p1, p2);
+        if area == 0.0 || !area.is_finite() {
+            continue;
+        }
+
+        let range = |a: f64, b: f64, c: f64, size: usize| {
+            let min = a.min(b).min(c).floor().max(0.0) as usize;
+            let max = a.max(b).max(c).ceil().min(size as f64) as usize;
+            min..max
+        };
+        for py in range(p0.1, p1.1, p2.1, height) {
+            for px in range(p0.0, p1.0, p2.0, width) {
+                let center = (px as f64 + 0.5, py as f64 + 0.5);
+                // Dividing by the area makes both windings positive inside
+                let mut weights = [
+                    edge(p1, p2, center) / area,
+                    edge(p2, p0, center) / area,
+                    edge(p0, p1, center) / area,
+                ];
+                if weights.iter().any(|&weight| weight < 0.0) {
+                    continue;
+                }
+                if !camera.is_parallel() {
+                    weights = [0, 1, 2].map(|i| weights[i] / polygon[fan[i]].0.z);
+                    let sum: f64 = weights.iter().sum();
+                    weights = weights.map(|weight| weight / sum);
+                }
+
+                let mut view = Vec3::new(0.0, 0.0, 0.0);
+                let mut barycentric = (0.0, 0.0);
+                for (weight, corner) in weights.into_iter().zip(fan) {
+                    let (corner_view, (b, c)) = polygon[corner];
+                    view = view + corner_view * weight;
+                    barycentric.0 += b * weight;
+                    barycentric.1 += c * weight;
+                }
+                plot(px, py, camera.depth(view), barycentric);
+            }
+        }
+    }
+}
+
+// Cuts off the part of a polygon in view space in front of the near plane,
+// with the weights of its corners carried over to the new ones
+fn clip_polygon_to_near_plane(
+    near_plane: f64,
+    polygon: &[(Vec3, (f64, f64))],
+) -> Vec<(Vec3, (f64, f64))> {
+    let mut clipped = Vec::new();
+    for (index, &(view, weights)) in polygon.iter().enumerate() {
+        let (next, next_weights) = polygon[(index + 1) % polygon.len()];
+        let inside = view.z >= near_plane;
+        if inside {
+            clipped.push((view, weights));
+        }
+        if inside != (next.z >= near_plane) {
+            let t = (near_plane - view.z) / (next.z - view.z);
+            clipped.push((
+                view + (next - view) * t,
+                (
+                    weights.0 + (next_weights.0 - weights.0) * t,
+                    weights.1 + (next_weights.1 - weights.1) * t,
+                ),
+            ));
+        }
+    }
+    clipped
+}
+
+// Texels along each side of the cube of a shadow map
+const SHADOW_MAP_SIZE: usize = 512;
+
+// The shadow maps of every light of a scene, kept for as long as the lights
+// and the opaque faces stay where they are
+#[derive(Debug, Clone, Default)]
+pub struct ShadowMaps {
+    scene: u64, // hash of the lights and the opaque faces
+    maps: Vec<ShadowMap>,
+}
+impl ShadowMaps {
+    // Renders the maps again when the scene casts different shadows
+    fn prepare(&mut self, scene: &Scene, objects: &[WorldTriangles]) -> &[ShadowMap] {
+        let mut hasher = DefaultHasher::new();
+        for object in objects {
+            if scene.objects[object.object].material.is_opaque() {
+                for vertex in object.triangles.iter().flatten() {
+                    [vertex.x, vertex.y, vertex.z]
+                        .map(f64::to_bits)
+                        .hash(&mut hasher);
+                }
+            }
+        }
+        for light in &scene.lights {
+            [light.position.x, light.position.y, light.position.z]
+                .map(f64::to_bits)
+                .hash(&mut hasher);
+        }
+        let hash = hasher.finish();
+
+        if self.scene != hash || self.maps.len() != scene.lights.len() {
+            *self = Self {
+                scene: hash,
+                maps: scene
+                    .lights
+                    .iter()
+                    .map(|light| ShadowMap::new(scene, objects, light.position))
+                    .collect(),
+            };
+        }
+        &self.maps
+    }
+}
+
+// The distance from a light to the nearest opaque face in every direction,
+// rendered into the six sides of a cube around it by cameras looking along
+// the axes
+#[derive(Debug, Clone)]
+struct ShadowMap {
+    sides: Vec<(Camera, Vec<f64>)>,
+}
+impl ShadowMap {
+    fn new(scene: &Scene, objects: &[WorldTriangles], position: Vec3) -> Self {
+        let sides = [
+            (0.0, 0.0),
+            (90.0, 0.0),
+            (180.0, 0.0),
+            (-90.0, 0.0),
+            (0.0, 90.0),
+            (0.0, -90.0),
+        ]
+        .into_iter()
+        .map(|(yaw, pitch)| {
+            let camera = Camera {
+                pos: position,
+                fov: 90.0,
+                aspect_ratio: 1.0,
+                projection: crate::camera::Projection::Perspective,
+                near_plane: 1e-3,
+                yaw,
+                pitch,
+                ..Camera::default()
+            };
+            let mut distances = vec![f64::INFINITY; SHADOW_MAP_SIZE * SHADOW_MAP_SIZE];
+            for object in objects {
+                if !scene.objects[object.object].material.is_opaque() {
+                    continue;
+                }
+                for &triangle in &object.triangles {
+                    let [a, b, c] = triangle;
+                    let size = SHADOW_MAP_SIZE;
+                    rasterize_triangle(&camera, triangle, size, size, |x, y, _, (wb, wc)| {
+                        let point = a * (1.0 - wb - wc) + b * wb + c * wc;
+                        let distance = &mut distances[y * size + x];
+                        *distance = distance.min((point - position).length());
+                    });
+                }
+            }
+            (camera, distances)
+        })
+        .collect();
+        Self { sides }
+    }
+
+    // The distance to the nearest face in the direction of a point, from the
+    // side of the cube that looks most directly at it
+    fn distance_towards(&self, point: Vec3) -> f64 {
+        let facing = |camera: &Camera| camera.forward().dot(point - camera.pos);
+        let Some((camera, distances)) = self
+            .sides
+            .iter()
+            .max_by(|a, b| facing(&a.0).total_cmp(&facing(&b.0)))
+        else {
+            return f64::INFINITY;
+        };
+        let (x, y) = camera.project_vertex(&point);
+        let texel = |ndc: f64| {
+            ((ndc + 1.0) / 2.0 * SHADOW_MAP_SIZE as f64).clamp(0.0, SHADOW_MAP_SIZE as f64 - 1.0)
+                as usize
+        };
+        distances[texel(-y) * SHADOW_MAP_SIZE + texel(x)]
+    }
+
+    // How much of a light reaches a point. Soft shadows first average the
+    // distance to the faces around the point that are in the way, to spread
+    // the samples wider the further the point is behind them (percentage
+    // closer soft shadows).
+    fn visibility(
+        &self,
+        light: &Light,
+        point: Vec3,
+        normal: Vec3,
+        shadows: Shadows,
+        noise: f64,
+    ) -> f64 {
+        let distance = (point - light.position).length();
+        // Texels cover more of the faces further from the light, so the point
+        // is moved off its face by more than one of them
+        let texel = 2.0 * distance / SHADOW_MAP_SIZE as f64;
+        let point = point + normal * (texel * 2.0);
+        let blocker = |point: Vec3| {
+            let nearest = self.distance_towards(point);
+            (nearest < (point - light.position).length() - texel).then_some(nearest)
+        };
+
+        match shadows {
+            Shadows::Off => 1.0,
+            Shadows::Hard => blocker(point).map_or(1.0, |_| 0.0),
+            Shadows::Soft(samples) => {
+                let direction = (light.position - point).normalize();
+                let side = if direction.x.abs() < 0.9 {
+                    Vec3::new(1.0, 0.0, 0.0)
+                } else {
+                    Vec3::new(0.0, 1.0, 0.0)
+                };
+                let u = direction.cross(side).normalize();
+                let v = direction.cross(u);
+                let samples = samples.max(1);
+                // Points on a disk around the point facing the light, in the
+                // same spiral as the soft shadows of the raycast mode
+                let disk = |radius: f64| {
+                    (0..samples).map(move |i| {
+                        let r = radius * ((i as f64 + 0.5) / samples as f64).sqrt();
+                        let angle = i as f64 * GOLDEN_ANGLE + noise * std::f64::consts::TAU;
+                        point + (u * angle.cos() + v * angle.sin()) * r
+                    })
+                };
+
+                let blockers: Vec<f64> = disk(light.radius).filter_map(blocker).collect();
+                if blockers.is_empty() {
+                    return 1.0;
+                }
+                let nearest = blockers.iter().sum::<f64>() / blockers.len() as f64;
+                let penumbra = light.radius * (distance - nearest).max(0.0) / nearest.max(texel);
+                let lit = disk(penumbra.min(distance))
+                    .filter(|&sample| blocker(sample).is_none())
+                    .count();
+                lit as f64 / samples as f64
+            }
+        }
+    }
+}
+
+// Marches rays through the signed distance fields of the scene shapes, which
+// stop at the meshes of the objects in between. Everything is drawn opaque.
+pub fn raymarch(scene: &Scene, camera: &Camera, shadows: Shadows, framebuffer: &mut Framebuffer) {
+    let objects = world_triangles(scene);
+    let (pixel_width, pixel_height) = (
+        2.0 / framebuffer.width as f64,
+        2.0 / framebuffer.height as f64,
+    );
+
+    for py in 0..framebuffer.height {
+        for px in 0..framebuffer.width {
+            let u = (px as f64 + 0.5) * pixel_width - 1.0;
+            let v = 1.0 - (py as f64 + 0.5) * pixel_height;
+            let Some((origin, ray)) = camera.ray(u, v) else {
+                continue;
+            };
+
+            let neighbours = [
+                camera.ray(u + pixel_width, v),
+                camera.ray(u, v - pixel_height),
+            ];
+            let Some(Surface {
+                t,
+                material,
+                base,
+                normal,
+            }) = nearest_surface(scene, &objects, origin, ray, camera.far_plane, neighbours)
+            else {
+                continue;
+            };
+            let point = origin + ray * t;
+            let depth = camera.depth(camera.apply_view_transform(point - camera.pos));
+            if depth < camera.near_plane {
+                continue;
+            }
+
+            let view = ray * -1.0;
+            let lights: Vec<(Vec3, [f64; 3])> = if scene.lights.is_empty() {
+                vec![(view, [1.0; 3])]
+            } else {
+                let noise = interleaved_noise(px, py);
+                scene
+                    .lights
+                    .iter()
+                    .map(|light| {
+                        let (direction, strength) =
+                            light_at(&objects, scene, light, point, normal, shadows, noise);
+                        let radius = match shadows {
+                            Shadows::Off => return (direction, strength),
+                            Shadows::Hard => None,
+                            Shadows::Soft(_) => Some(light.radius),
+                        };
+                        let start = point + normal * 1e-3;
+                        let visible = sdf::shadow(&scene.shapes, start, light.position, radius);
+                        (direction, strength.map(|channel| channel * visible))
+                    })
+                    .collect()
+            };
+            let occlusion = sdf::ambient_occlusion(&scene.shapes, point, normal);
+            let color = material
+                .shade(base, normal, view, &lights)
+                .map(|channel| (channel as f64 * occlusion).round() as u8);
+            framebuffer.plot(px as i64, py as i64, depth, color, 1.0);
+        }
+    }
+}
+
+// What a ray hits first, with the normal turned towards it
+struct Surface<'a> {
+    t: f64,
+    material: &'a Material,
+    base: [u8; 3], // the color of the material or its texture
+    normal: Vec3,
+}
+
+// The nearest shape or mesh along a ray, up to `max_t` away for shapes
+fn nearest_surface<'a>(
+    scene: &'a Scene,
+    objects: &[WorldTriangles],
+    origin: Vec3,
+    ray: Vec3,
+    max_t: f64,
+    neighbours: [Option<(Vec3, Vec3)>; 2],
+) -> Option<Surface<'a>> {
+    let mesh_hit = nearest_hit(objects, origin, ray);
+    let max_t = mesh_hit.map_or(max_t, |hit| hit.t);
+    let (t, material, base, normal) =
+        match (sdf::march(&scene.shapes, origin, ray, max_t), mesh_hit) {
+            (Some((t, index)), _) => {
+                let shape = &scene.shapes[index];
+                let base = color::to_rgb(shape.material.color, [255, 0, 0]);
+                (t, &shape.material, base, shape.normal(origin + ray * t))
+            }
+            (None, Some(hit)) => {
+                let material = &scene.objects[hit.object].material;
+                let base = texture_color(scene, objects, &hit, neighbours)
+                    .unwrap_or_else(|| color::to_rgb(material.color, [255, 0, 0]));
+                (hit.t, material, base, hit.normal)
+            }
+            (None, None) => return None,
+        };
+    let normal = if normal.dot(ray) > 0.0 {
+        normal * -1.0
+    } else {
+        normal
+    };
+    Some(Surface {
+        t,
+        material,
+        base,
+        normal,
+    })
+}
+
+// The samples of every pixel summed up over frames, for as long as the camera,
+// the scene and the size of the frame stay the same
+#[derive(Debug, Clone, Default)]
+pub struct Accumulation {
+    pub samples: usize, // per pixel so far
+    camera: Option<Camera>,
+    scene: u64, // hash of everything in the scene that changes the picture
+    sums: Vec<[f64; 3]>,
+    hits: Vec<usize>, // samples that hit anything
+    depths: Vec<f64>, // of the nearest hit
+}
+impl Accumulation {
+    // Starts over when the samples so far are of a different picture
+    fn prepare(&mut self, scene: &Scene, camera: &Camera, pixels: usize) {
+        let hash = scene_hash(scene);
+        if self.camera.as_ref() == Some(camera) && self.scene == hash && self.sums.len() == pixels {
+            return;
+        }
+        *self = Self {
+            samples: 0,
+            camera: Some(*camera),
+            scene: hash,
+            sums: vec![[0.0; 3]; pixels],
+            hits: vec![0; pixels],
+            depths: vec![f64::INFINITY; pixels],
+        };
+    }
+}
+
+fn scene_hash(scene: &Scene) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let hash_material = |material: &Material, hasher: &mut DefaultHasher| {
+        format!(
+            "{:?} {:?} {} {} {}",
+            material.color, material.emissive, material.opacity, material.metal, material.roughness
+        )
+        .hash(hasher);
+        material.texture.as_ref().map(Arc::as_ptr).hash(hasher);
+    };
+    for object in &scene.objects {
+        for vertex in object.world_vertices() {
+            [vertex.x, vertex.y, vertex.z]
+                .map(f64::to_bits)
+                .hash(&mut hasher);
+        }
+        object.mesh.faces.hash(&mut hasher);
+        hash_material(&object.material, &mut hasher);
+    }
+    for shape in &scene.shapes {
+        format!("{:?}", shape.steps).hash(&mut hasher);
+        hash_material(&shape.material, &mut hasher);
+    }
+    format!("{:?}", scene.lights).hash(&mut hasher);
+    hasher.finish()
+}
+
+const MAX_BOUNCES: usize = 5;
+// Light coming from all around the scene, as bright as the ambient light of
+// the raycast mode
+const SKY: f64 = 0.2;
+
+// Adds `samples` paths through every pixel to the accumulated ones and draws
+// their average. Paths bounce off surfaces in random directions, scattered
+// evenly or mirrored by metals, and pick up the light of emissive surfaces and
+// the sky. Lights are points that random bounces never hit, so every
+// scattering surface samples them directly, at a random point across their
+// radius, which makes soft shadows. Everything is opaque.
+pub fn path_trace(
+    scene: &Scene,
+    camera: &Camera,
+    samples: usize,
+    accumulation: &mut Accumulation,
+    framebuffer: &mut Framebuffer,
+) {
+    let objects = world_triangles(scene);
+    accumulation.prepare(scene, camera, framebuffer.width * framebuffer.height);
+    let (pixel_width, pixel_height) = (
+        2.0 / framebuffer.width as f64,
+        2.0 / framebuffer.height as f64,
+    );
+    // Without lights, a light at the camera lights the scene
+    let headlight = Light {
+        position: camera.pos,
+        radius: 0.0,
+        ..Light::new("headlight")
+    };
+    let lights = if scene.lights.is_empty() {
+        std::slice::from_ref(&headlight)
+    } else {
+        &scene.lights[..]
+    };
+
+    for py in 0..framebuffer.height {
+        for px in 0..framebuffer.width {
+            let index = framebuffer.index(px, py);
+            for sample in 0..samples {
+                let mut rng = Rng::new(index, accumulation.samples + sample);
+                // Spread over the pixel, which smooths the edges
+                let u = (px as f64 + rng.next()) * pixel_width - 1.0;
+                let v = 1.0 - (py as f64 + rng.next()) * pixel_height;
+                let Some((origin, ray)) = camera.ray(u, v) else {
+                    continue;
+                };
+                let Some((t, color)) =
+                    trace(scene, &objects, lights, origin, ray, camera, &mut rng)
+                else {
+                    continue;
+                };
+                let sum = &mut accumulation.sums[index];
+                (0..3).for_each(|c| sum[c] += color[c]);
+                accumulation.hits[index] += 1;
+                let depth =
+                    camera.depth(camera.apply_view_transform(origin + ray * t - camera.pos));
+                accumulation.depths[index] = accumulation.depths[index].min(depth);
+            }
+
+            let hits = accumulation.hits[index];
+            if hits > 0 {
+                let color = accumulation.sums[index]
+                    .map(|c| (c / hits as f64 * 255.0).round().clamp(0.0, 255.0) as u8);
+                let coverage = hits as f64 / (accumulation.samples + samples) as f64;
+                let depth = accumulation.depths[index];
+                framebuffer.plot(px as i64, py as i64, depth, color, coverage as f32);
+            }
+        }
+    }
+    accumulation.samples += samples;
+}
+
+// The light along a path from the camera, and how far away its first
+// surface is. None when it hits nothing.
+fn trace(
+    scene: &Scene,
+    objects: &[WorldTriangles],
+    lights: &[Light],
+    origin: Vec3,
+    ray: Vec3,
+    camera: &Camera,
+    rng: &mut Rng,
+) -> Option<(f64, [f64; 3])> {
+    let (mut origin, mut ray) = (origin, ray);
+    let mut throughput = [1.0; 3]; // of each channel, left after the bounces so far
+    let mut light = [0.0; 3];
+    let mut first = None;
+
+    for _ in 0..MAX_BOUNCES {
+        let surface = nearest_surface(scene, objects, origin, ray, camera.far_plane, [None; 2]);
+        let Some(Surface {
+            t,
+            material,
+            base,
+            normal,
+        }) = surface
+        else {
+            if first.is_some() {
+                (0..3).for_each(|c| light[c] += throughput[c] * SKY);
+            }
+            break;
+        };
+        if first.is_none() {
+            let depth = camera.depth(camera.apply_view_transform(origin + ray * t - camera.pos));
+            if depth < camera.near_plane {
+                return None;
+            }
+            first = Some(t);
+        }
+        let point = origin + ray * t;
+        let emissive = color::to_rgb(material.emissive, [0, 0, 0]);
+        let albedo = base.map(|c| c as f64 / 255.0);
+        (0..3).for_each(|c| light[c] += throughput[c] * emissive[c] as f64 / 255.0);
+        // Off the surface, so the next ray doesn't hit it right away
+        let start = point + normal * 1e-3;
+
+        if rng.next() < material.metal {
+            let mirrored = ray - normal * (2.0 * ray.dot(normal));
+            ray = (mirrored + random_unit_vector(rng) * material.roughness).normalize();
+            if ray.dot(normal) <= 0.0 {
+                break;
+            }
+        } else {
+            for light_source in lights {
+                let target = light_source.position + random_unit_vector(rng) * light_source.radius;
+                let direction = (target - point).normalize();
+                let facing = normal.dot(direction);
+                if facing <= 0.0 {
+                    continue;
+                }
+                let visible = transmittance(objects, scene, start, target)
+                    * sdf::shadow(&scene.shapes, start, target, None);
+                let strength = color::to_rgb(light_source.color, [255, 255, 255])
+                    .map(|channel| channel as f64 / 255.0 * light_source.intensity);
+                for c in 0..3 {
+                    light[c] += throughput[c] * albedo[c] * facing * strength[c] * visible;
+                }
+            }
+            // More often close to the normal, as light scatters off surfaces
+            ray = (normal + random_unit_vector(rng)).normalize();
+            if ray.length() == 0.0 {
+                ray = normal;
+            }
+        }
+        (0..3).for_each(|c| throughput[c] *= albedo[c]);
+        origin = start;
+    }
+    first.map(|t| (t, light))
+}
+
+// Random numbers from 0 to 1, the same ones for the same pixel and sample
+struct Rng(u64);
+impl Rng {
+    fn new(pixel: usize, sample: usize) -> Self {
+        // splitmix64, so neighbouring seeds start far apart
+        let mut z = ((pixel as u64) << 32) ^ sample as u64;
+        z = z.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        Self((z ^ (z >> 31)) | 1)
+    }
+
+    // xorshift64*
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545f4914f6cdd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// A point on the unit sphere
+fn random_unit_vector(rng: &mut Rng) -> Vec3 {
+    let z = 2.0 * rng.next() - 1.0;
+    let angle = std::f64::consts::TAU * rng.next();
+    let radius = (1.0 - z * z).sqrt();
+    Vec3::new(radius * angle.cos(), radius * angle.sin(), z)
+}
+
+// The direction from a point towards a light and how much of each channel of
+// the light reaches it
+fn light_at(
+    objects: &[WorldTriangles],
+    scene: &Scene,
+    light: &Light,
+    point: Vec3,
+    normal: Vec3,
+    shadows: Shadows,
+    noise: f64,
+) -> (Vec3, [f64; 3]) {
+    let direction = (light.position - point).normalize();
+    let strength = light_strength(light);
+    // Lights behind the face don't light it anyway
+    if normal.dot(direction) <= 0.0 {
+        return (direction, strength);
+    }
+
+    // Off the surface, so the rays don't hit the face they start on
+    let start = point + normal * 1e-6;
+    let visible = match shadows {
+        Shadows::Off => 1.0,
+        Shadows::Hard => transmittance(objects, scene, start, light.position),
+        Shadows::Soft(samples) => {
+            // Points on a disk facing the surface, in a spiral turned by the
+            // noise of the pixel, so neighbouring pixels fill in each other's
+            // gaps instead of repeating the same bands
+            let side = if direction.x.abs() < 0.9 {
+                Vec3::new(1.0, 0.0, 0.0)
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            };
+            let u = direction.cross(side).normalize();
+            let v = direction.cross(u);
+            let samples = samples.max(1);
+            let total: f64 = (0..samples)
+                .map(|i| {
+                    let radius = light.radius * ((i as f64 + 0.5) / samples as f64).sqrt();
+                    let angle = i as f64 * GOLDEN_ANGLE + noise * std::f64::consts::TAU;
+                    let target = light.position + (u * angle.cos() + v * angle.sin()) * radius;
+                    transmittance(objects, scene, start, target)
+                })
+                .sum();
+            total / samples as f64
+        }
+    };
+    (direction, strength.map(|channel| channel * visible))
+}
+
+// The strength of each channel of a light
+fn light_strength(light: &Light) -> [f64; 3] {
+    color::to_rgb(light.color, [255, 255, 255])
+        .map(|channel| channel as f64 / 255.0 * light.intensity)
+}
+
+const GOLDEN_ANGLE: f64 = 2.399963229728653;
+
+// How much light gets from one point to another, faces in between let
+// through as much as they are transparent
+fn transmittance(objects: &[WorldTriangles], scene: &Scene, from: Vec3, to: Vec3) -> f64 {
+    let distance = (to - from).length();
+    let ray = (to - from).normalize();
+    let mut light = 1.0;
+    let mut start = 0.0;
+    for _ in 0..MAX_LAYERS {
+        let Some(hit) = nearest_hit(objects, from + ray * start, ray) else {
+            break;
+        };
+        if start + hit.t >= distance {
+            break;
+        }
+        light *= 1.0 - scene.objects[hit.object].material.opacity;
+        if light < 0.01 {
+            return 0.0;
+        }
+        start += hit.t + 1e-6;
+    }
+    light
+}
+
+// A different number from 0 to 1 for each pixel, spread evenly enough that
+// neighbouring pixels rarely get close ones
+fn interleaved_noise(x: usize, y: usize) -> f64 {
+    (52.9829189 * (0.06711056 * x as f64 + 0.00583715 * y as f64).fract()).fract()
+}
+
+// The texel a hit lands on, picking the mipmap by how far apart the texture
+// coordinates of the neighbouring pixels are on the plane of the same face
+fn texture_color(
+    scene: &Scene,
+    objects: &[WorldTriangles],
+    hit: &Hit,
+    neighbours: [Option<(Vec3, Vec3)>; 2],
+) -> Option<[u8; 3]> {
+    let object = &scene.objects[hit.object];
+    let texture = object.material.texture.as_ref()?;
+    let uvs = object
+        .mesh
+        .face_uvs
+        .get(hit.face)?
+        .map(|index| object.mesh.uvs[index]);
+    let uv_at = |(b, c): (f64, f64)| {
+        [0, 1].map(|axis| uvs[0][axis] * (1.0 - b - c) + uvs[1][axis] * b + uvs[2][axis] * c)
+    };
+    let [u, v] = uv_at(hit.barycentric);
+
+    let [a, b, c] = objects
+        .iter()
+        .find(|triangles| triangles.object == hit.object)?
+        .triangles[hit.face];
+    let normal = (b - a).cross(c - a);
+    let footprint = neighbours
+        .into_iter()
+        .flatten()
+        .filter_map(|(origin, ray)| {
+            let t = (a - origin).dot(normal) / ray.dot(normal);
+            t.is_finite().then(|| {
+                let [nu, nv] = uv_at(barycentric(origin + ray * t, a, b, c));
+                (nu - u).hypot(nv - v)
+            })
+        })
+        .fold(0.0, f64::max);
+
+    Some(texture.sample(u, v, footprint))
+}
+
+// The object under a point of the screen in normalized device coordinates
+pub fn pick(scene: &Scene, camera: &Camera, u: f64, v: f64) -> Option<Hit> {
+    let (origin, ray) = camera.ray(u, v)?;
+    nearest_hit(&world_triangles(scene), origin, ray)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub object: usize, // index into the scene objects
+    pub t: f64,        // distance along the ray
+    pub normal: Vec3,
+    pub face: usize, // index into the mesh faces
+    // Weights of the second and third corner of the face at the hit
+    pub barycentric: (f64, f64),
+}
+
+// Triangles and bounds of an object in world space, so each ray only tests the
+// objects whose bounding box it passes through
+struct WorldTriangles {
+    object: usize,
+    min: Vec3,
+    max: Vec3,
+    triangles: Vec<[Vec3; 3]>,
+}
+
+fn world_triangles(scene: &Scene) -> Vec<WorldTriangles> {
+    scene
+        .objects
+        .iter()
+        .enumerate()
+        .filter(|(_, object)| !object.mesh.faces.is_empty())
+        .map(|(index, object)| {
+            let vertices = object.world_vertices();
+            let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+            let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+            for vertex in &vertices {
+                min = Vec3::new(
+                    min.x.min(vertex.x),
+                    min.y.min(vertex.y),
+                    min.z.min(vertex.z),
+                );
+                max = Vec3::new(
+                    max.x.max(vertex.x),
+                    max.y.max(vertex.y),
+                    max.z.max(vertex.z),
+                );
+            }
+            let triangles = object
+                .mesh
+                .faces
+                .iter()
+                .map(|&[a, b, c]| [vertices[a], vertices[b], vertices[c]])
+                .collect();
+            WorldTriangles {
+                object: index,
+                min,
+                max,
+                triangles,
+            }
+        })
+        .collect()
+}
+
+fn nearest_hit(objects: &[WorldTriangles], origin: Vec3, ray: Vec3) -> Option<Hit> {
+    let mut nearest: Option<Hit> = None;
+    for object in objects {
+        if !ray_hits_aabb(origin, ray, object.min, object.max) {
+            continue;
+        }
+        for (face, &[a, b, c]) in object.triangles.iter().enumerate() {
+            let Some((t, barycentric)) = intersect(origin, ray, a, b, c) else {
+                continue;
+            };
+            if nearest.is_none_or(|nearest| t < nearest.t) {
+                nearest = Some(Hit {
+                    object: object.object,
+                    t,
+                    normal: (b - a).cross(c - a).normalize(),
+                    face,
+                    barycentric,
+                });
+            }
+        }
+    }
+    nearest
+}
+
+// Möller–Trumbore, returns the distance along the ray to the hit
+pub fn intersect_triangle(origin: Vec3, dir: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f64> {
+    intersect(origin, dir, a, b, c).map(|(t, _)| t)
+}
+
+// Along with the weights of `b` and `c` at the hit
+fn intersect(origin: Vec3, dir: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<(f64, (f64, f64))> {
+    const EPSILON: f64 = 1e-9;
+    let edge1 = b - a;
+    let edge2 = c - a;
+
+    let p = dir.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < EPSILON {
+        return None; // parallel to the triangle
+    }
+    let inv_det = 1.0 / det;
+
+    let s = origin - a;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = dir.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_det;
+    (t > EPSILON).then_some((t, (u, v)))
+}
+
+// The weights of `b` and `c` for a point in the plane of the triangle, which
+// may be outside of it
+fn barycentric(point: Vec3, a: Vec3, b: Vec3, c: Vec3) -> (f64, f64) {
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let (d00, d01, d11) = (ab.dot(ab), ab.dot(ac), ac.dot(ac));
+    let (d20, d21) = (ap.dot(ab), ap.dot(ac));
+    let denominator = d00 * d11 - d01 * d01;
+    (
+        (d11 * d20 - d01 * d21) / denominator,
+        (d00 * d21 - d01 * d20) / denominator,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Projection;
+    use crate::mesh::Mesh;
+    use proptest::prelude::*;
+
+    fn clip(x1: f64, y1: f64, x2: f64, y2: f64) -> Option<(f64, f64, f64, f64)> {
+        clip_line_to_viewport(x1, y1, x2, y2, -1.0, 1.0, -1.0, 1.0)
+    }
+
+    #[test]
+    fn clip_keeps_inside_line() {
+        assert_eq!(clip(-0.5, -0.5, 0.5, 0.25), Some((-0.5, -0.5, 0.5, 0.25)));
+    }
+
+    #[test]
+    fn clip_rejects_outside_line() {
+        assert_eq!(clip(1.5, -2.0, 1.5, 2.0), None);
+        assert_eq!(clip(-3.0, 2.0, 3.0, 2.0), None);
+        assert_eq!(clip(0.5, 2.0, 2.0, 0.5), None);
+    }
+
+    #[test]
+    fn clip_shortens_crossing_line() {
+        assert_eq!(clip(0.0, 0.0, 2.0, 0.0), Some((0.0, 0.0, 1.0, 0.0)));
+        assert_eq!(clip(-2.0, -2.0, 2.0, 2.0), Some((-1.0, -1.0, 1.0, 1.0)));
+        assert_eq!(clip(0.0, 3.0, 0.0, -3.0), Some((0.0, 1.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn ray_hits_triangle_in_front() {
+        let a = Vec3::new(-1.0, -1.0, 2.0);
+        let b = Vec3::new(1.0, -1.0, 2.0);
+        let c = Vec3::new(0.0, 1.0, 2.0);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        let t = intersect_triangle(origin, Vec3::new(0.0, 0.0, 1.0), a, b, c);
+        assert!(t.is_some_and(|t| (t - 2.0).abs() < 1e-9));
+        // behind the origin and beside the triangle
+        assert_eq!(
+            intersect_triangle(origin, Vec3::new(0.0, 0.0, -1.0), a, b, c),
+            None
+        );
+        assert_eq!(
+            intersect_triangle(origin, Vec3::new(1.0, 0.0, 1.0).normalize(), a, b, c),
+            None
+        );
+    }
+
+    #[test]
+    fn pick_finds_nearest_object() {
+        let mut far = Object::new(Mesh::cube());
+        far.transform.position = Vec3::new(0.0, 0.0, 8.0);
+        let mut near = Object::new(Mesh::cube());
+        near.transform.position = Vec3::new(0.0, 0.0, 4.0);
+        let scene = Scene {
+            objects: vec![far, near],
+            shapes: Vec::new(),
+            lights: Vec::new(),
+        };
+        let camera = Camera {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            aspect_ratio: 1.0,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            pick(&scene, &camera, 0.0, 0.0).map(|hit| hit.object),
+            Some(1)
+        );
+        assert!(pick(&scene, &camera, 0.9, 0.9).is_none());
+    }
+
+    #[test]
+    fn raycast_maps_textures_onto_faces() {
+        let source = "v -1 -1 1\nv -1 1 1\nv 1 1 1\nv 1 -1 1\n\
+                      vt 0 0\nvt 0 1\nvt 1 1\nvt 1 0\nf 1/1 2/2 3/3 4/4\n";
+        let mut object = Object::new(Mesh::parse_obj("quad".to_string(), source).unwrap());
+        let image = crate::headless::Image::parse_pnm(b"P2 2 2 1 1 0 0 1").unwrap();
+        let mut texture = crate::texture::Texture::new(image).unwrap();
+        texture.filter = crate::texture::Filter::Nearest;
+        object.material.texture = Some(std::sync::Arc::new(texture));
+        let scene = Scene {
+            objects: vec![object],
+            shapes: Vec::new(),
+            lights: Vec::new(),
+        };
+        let camera = Camera {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            aspect_ratio: 1.0,
+            projection: Projection::Orthographic,
+            extent: 1.0,
+            ..Default::default()
+        };
+
+        let mut framebuffer = Framebuffer::new(4, 4, [0, 0, 255]);
+        raycast(&scene, &camera, Shadows::Hard, &mut framebuffer);
+        let at = |x, y| framebuffer.color[framebuffer.index(x, y)];
+        assert_eq!(
+            [at(0, 0), at(3, 0), at(0, 3), at(3, 3)],
+            [[255; 3], [0; 3], [0; 3], [255; 3]]
+        );
+    }
+
+    #[test]
+    fn raymarch_draws_shapes_and_meshes_by_depth() {
+        let source = "object cube cube\nposition -0.25 0 1\nscale 2\ncolor blue\n\
+                      sdf ball\nsphere 0 0 2 0.5\ncolor 0 255 0\n";
+        let scene = Scene::parse_scene(source, std::path::Path::new(".")).unwrap();
+        let camera = Camera {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            aspect_ratio: 1.0,
+            projection: Projection::Orthographic,
+            extent: 1.0,
+            ..Default::default()
+        };
+
+        let mut framebuffer = Framebuffer::new(8, 8, [0, 0, 0]);
+        raymarch(&scene, &camera, Shadows::Hard, &mut framebuffer);
+        // The cube covers the left half of the ball, the corners are empty
+        let color = |x, y| framebuffer.color[framebuffer.index(x, y)];
+        assert_eq!(color(3, 4), [0, 0, 238]);
+        let [red, green, blue] = color(4, 4);
+        assert!(red == 0 && green > 200 && blue == 0, "{:?}", color(4, 4));
+        assert_eq!(framebuffer.coverage[framebuffer.index(7, 0)], 0.0);
+    }
+
+    #[test]
+    fn path_tracing_accumulates_until_the_camera_moves() {
+        // Glows white without reflecting any light, next to a mirror
+        let source = "sdf lamp\nsphere -0.5 0 2 0.4\ncolor black\nemissive white\n\
+                      sdf mirror\nbox 0.6 0 2 0.2 1 1\ncolor white\nmetal 1\n";
+        let scene = Scene::parse_scene(source, std::path::Path::new(".")).unwrap();
+        let mut camera = Camera {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            aspect_ratio: 1.0,
+            projection: Projection::Orthographic,
+            extent: 1.0,
+            ..Default::default()
+        };
+        let mut accumulation = Accumulation::default();
+        let mut framebuffer = Framebuffer::new(8, 8, [0, 0, 0]);
+        path_trace(&scene, &camera, 2, &mut accumulation, &mut framebuffer);
+        path_trace(&scene, &camera, 3, &mut accumulation, &mut framebuffer);
+        assert_eq!(accumulation.samples, 5);
+        assert_eq!(framebuffer.color[framebuffer.index(2, 4)], [255; 3]);
+        // Mirrors only reflect, here the sky behind the camera
+        assert_eq!(framebuffer.color[framebuffer.index(6, 4)], [51; 3]);
+        assert_eq!(framebuffer.coverage[framebuffer.index(0, 0)], 0.0);
+
+        camera.pos.y += 0.1;
+        path_trace(&scene, &camera, 1, &mut accumulation, &mut framebuffer);
+        assert_eq!(accumulation.samples, 1);
+    }
+
+    #[test]
+    fn lights_cast_shadows() {
+        // A small square in front of a wall, lit from in front of both
+        let source = "v -1 -1 2\nv -1 1 2\nv 1 1 2\nv 1 -1 2\n\
+                      v -0.2 -0.2 1\nv -0.2 0.2 1\nv 0.2 0.2 1\nv 0.2 -0.2 1\n\
+                      f 1 2 3 4\nf 5 6 7 8\n";
+        let mesh = Mesh::parse_obj("wall".to_string(), source).unwrap();
+        let mut scene = Scene::from_mesh(mesh);
+        scene.lights.push(crate::scene::Light::new("sun"));
+        let camera = Camera {
+            pos: Vec3::new(0.0, 0.0, -1.0),
+            aspect_ratio: 1.0,
+            projection: Projection::Orthographic,
+            extent: 1.0,
+            ..Default::default()
+        };
+        // Rays to the light in the raycast mode, shadow maps when rasterized
+        for rasterized in [false, true] {
+            let brightness = |shadows| {
+                let mut framebuffer = Framebuffer::new(10, 10, [0, 0, 0]);
+                if rasterized {
+                    let mut shadow_maps = ShadowMaps::default();
+                    rasterize(&scene, &camera, shadows, &mut shadow_maps, &mut framebuffer);
+                } else {
+                    raycast(&scene, &camera, shadows, &mut framebuffer);
+                }
+                // Beside the square, inside and outside of its shadow on the wall
+                [6, 8].map(|x| framebuffer.color[framebuffer.index(x, 5)][0])
+            };
+
+            let [unshadowed, lit] = brightness(Shadows::Off);
+            let [hard, hard_lit] = brightness(Shadows::Hard);
+            let [soft, _] = brightness(Shadows::Soft(64));
+            assert_eq!(hard_lit, lit);
+            assert!(hard < lit / 2 && unshadowed > lit, "{} {}", hard, lit);
+            // a light as large as the square lets some light around it
+            assert!(
+                hard < soft && soft < unshadowed,
+                "{} {} {}",
+                hard,
+                soft,
+                unshadowed
+            );
+        }
+    }
+
+    #[test]
+    fn shadow_maps_are_kept_until_the_lights_move() {
+        let mut scene = Scene::default();
+        scene.lights.push(crate::scene::Light::new("sun"));
+        scene.lights[0].position = Vec3::new(0.0, 0.0, -2.0);
+        let objects = world_triangles(&scene);
+        let mut shadow_maps = ShadowMaps::default();
+        let maps = shadow_maps.prepare(&scene, &objects).as_ptr();
+        assert_eq!(shadow_maps.prepare(&scene, &objects).as_ptr(), maps);
+
+        let distances = shadow_maps.maps[0].sides[0].1.clone();
+        scene.lights[0].position = Vec3::new(1.0, 0.0, -2.0);
+        shadow_maps.prepare(&scene, &objects);
+        assert_ne!(shadow_maps.maps[0].sides[0].1, distances);
+    }
+
+    #[test]
+    fn rasterized_faces_match_the_raycast_ones() {
+        let scene = Scene::default();
+        for projection in [Projection::Perspective, Projection::Orthographic] {
+            let camera = Camera {
+                pos: Vec3::new(-0.6, 0.4, -0.6),
+                yaw: 45.0,
+                pitch: -25.0,
+                aspect_ratio: 1.0,
+                projection,
+                extent: 0.3,
+                ..Default::default()
+            };
+            let mut raycast_buffer = Framebuffer::new(40, 40, [0, 0, 0]);
+            raycast(&scene, &camera, Shadows::Off, &mut raycast_buffer);
+            let mut solid = Framebuffer::new(40, 40, [0, 0, 0]);
+            rasterize(
+                &scene,
+                &camera,
+                Shadows::Off,
+                &mut ShadowMaps::default(),
+                &mut solid,
+            );
+
+            // Only pixels right on the edges of faces may differ
+            let differ = (0..solid.color.len())
+                .filter(|&i| {
+                    let [a, b] = [&raycast_buffer, &solid].map(|buffer| buffer.color[i]);
+                    a.iter().zip(b).any(|(a, b)| a.abs_diff(b) > 1)
+                })
+                .count();
+            assert!(differ < 40, "{:?}: {} pixels differ", projection, differ);
+            let covered = solid.coverage.iter().filter(|&&c| c > 0.0).count();
+            assert!(covered > 150, "{:?}: {} pixels", projection, covered);
+        }
+    }
+
+    #[test]
+    fn near_plane_clips_faces_into_polygons() {
+        let triangle = [
+            (Vec3::new(0.0, 0.0, 0.0), (0.0, 0.0)),
+            (Vec3::new(1.0, 0.0, 2.0), (1.0, 0.0)),
+            (Vec3::new(-1.0, 0.0, 2.0), (0.0, 1.0)),
+        ];
+        let clipped = clip_polygon_to_near_plane(1.0, &triangle);
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|(view, _)| view.z >= 1.0));
+        assert_eq!(clipped[0].1, (0.5, 0.0));
+        assert!(clip_polygon_to_near_plane(3.0, &triangle).is_empty());
+    }
+
+    #[test]
+    fn panoramic_lines_bend_and_break_at_the_seam() {
+        let camera = Camera {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            aspect_ratio: 1.0,
+            projection: Projection::Equirectangular,
+            ..Default::default()
+        };
+        // a line to the right of the camera, passing from the front to behind it
+        let segments = project_line(
+            &camera,
+            Vec3::new(1.0, 1.0, 3.0),
+            Vec3::new(1.0, 1.0, -3.0),
+            Color::Red,
+        );
+        assert!(segments.len() > 4);
+        for pair in segments.windows(2) {
+            assert!((pair[0].x2 - pair[1].x1).abs() < 1e-9);
+        }
+
+        // straight behind the camera it crosses from the right edge to the left
+        let segments = project_line(
+            &camera,
+            Vec3::new(1.0, 0.0, -3.0),
+            Vec3::new(-1.0, 0.0, -3.0),
+            Color::Red,
+        );
+        assert!(!segments.is_empty());
+        assert!(segments
+            .iter()
+            .all(|segment| (segment.x2 - segment.x1).abs() < 0.1));
+    }
+
+    fn coord() -> impl Strategy<Value = f64> {
+        -5.0..5.0f64
+    }
+
+    proptest! {
+        #[test]
+        fn clipped_line_is_inside_viewport(x1 in coord(), y1 in coord(), x2 in coord(), y2 in coord()) {
+            if let Some((cx1, cy1, cx2, cy2)) = clip(x1, y1, x2, y2) {
+                for c in [cx1, cy1, cx2, cy2] {
+                    prop_assert!((-1.0 - 1e-9..=1.0 + 1e-9).contains(&c));
+                }
+            }
+        }
+
+        #[test]
+        fn clipped_line_lies_on_original(x1 in coord(), y1 in coord(), x2 in coord(), y2 in coord()) {
+            if let Some((cx1, cy1, cx2, cy2)) = clip(x1, y1, x2, y2) {
+                for (px, py) in [(cx1, cy1), (cx2, cy2)] {
+                    let cross = (x2 - x1) * (py - y1) - (y2 - y1) * (px - x1);
+                    prop_assert!(cross.abs() < 1e-6);
+                }
+            }
+        }
+
+        #[test]
+        fn line_with_inside_point_is_kept(x1 in -1.0..1.0f64, y1 in -1.0..1.0f64, x2 in coord(), y2 in coord()) {
+            let (cx1, cy1, _, _) = clip(x1, y1, x2, y2).unwrap();
+            prop_assert!((cx1 - x1).abs() < 1e-12 && (cy1 - y1).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,639 @@
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::sdf::{self, Shape};
+use crate::texture::{Filter, Texture, Wrap};
+use crate::types::Vec3;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use ratatui::style::Color;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub position: Vec3,
+    pub rotation: Vec3, // euler angles in degrees, applied in X, Y, Z order
+    pub scale: Vec3,
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Vec3::new(0.0, 0.0, 0.0),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+impl Transform {
+    pub fn apply(&self, point: Vec3) -> Vec3 {
+        let (sx, cx) = self.rotation.x.to_radians().sin_cos();
+        let (sy, cy) = self.rotation.y.to_radians().sin_cos();
+        let (sz, cz) = self.rotation.z.to_radians().sin_cos();
+
+        let p = Vec3::new(
+            point.x * self.scale.x,
+            point.y * self.scale.y,
+            point.z * self.scale.z,
+        );
+        let p = Vec3::new(p.x, p.y * cx - p.z * sx, p.y * sx + p.z * cx);
+        let p = Vec3::new(p.x * cy + p.z * sy, p.y, -p.x * sy + p.z * cy);
+        let p = Vec3::new(p.x * cz - p.y * sz, p.x * sz + p.y * cz, p.z);
+
+        p + self.position
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Object {
+    pub name: String,
+    pub mesh: Mesh,
+    pub transform: Transform,
+    pub material: Material,
+}
+impl Object {
+    pub fn new(mesh: Mesh) -> Self {
+        Self {
+            name: mesh.name.clone(),
+            material: mesh.material.clone().unwrap_or_default(),
+            mesh,
+            transform: Transform::default(),
+        }
+    }
+
+    pub fn world_vertices(&self) -> Vec<Vec3> {
+        self.mesh
+            .vertices
+            .iter()
+            .map(|&vertex| self.transform.apply(vertex))
+            .collect()
+    }
+}
+
+// A point light, or a round one for soft shadows
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub name: String,
+    pub position: Vec3,
+    pub color: Color,
+    pub intensity: f64,
+    pub radius: f64, // soft shadows are sampled across it
+}
+impl Light {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            position: Vec3::new(0.0, 0.0, 0.0),
+            color: Color::White,
+            intensity: 1.0,
+            radius: 0.25,
+        }
+    }
+}
+
+// Without lights, a light at the camera lights the scene
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub objects: Vec<Object>,
+    pub shapes: Vec<Shape>, // only drawn by the raymarch mode
+    pub lights: Vec<Light>,
+}
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            objects: vec![Object::new(Mesh::cube())],
+            shapes: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+}
+impl Scene {
+    pub fn from_mesh(mesh: Mesh) -> Self {
+        Self {
+            objects: vec![Object::new(mesh)],
+            shapes: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+
+    // Loads either a single OBJ model or a scene file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let is_obj = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("obj"));
+
+        if is_obj {
+            Mesh::load_obj(path).map(Self::from_mesh)
+        } else {
+            Self::load_scene(path)
+        }
+    }
+
+    pub fn load_scene(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("could not read {}", path.display()))?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        Self::parse_scene(&source, base_dir).wrap_err_with(|| format!("in {}", path.display()))
+    }
+
+    // Scene files are line based. An `object <name> <mesh>` line starts a new
+    // object, where mesh is either a builtin shape or a path to an OBJ file
+    // relative to the scene file. Following `position`, `rotation` and
+    // `scale` lines apply to the last object, and so do the lines setting its
+    // material, see `parse_material_line`. A `light <name>` line starts a
+    // light instead, with `position`, `color`, `intensity` and `radius` lines.
+    // An `sdf <name>` line starts a signed distance field shape, built from
+    // the primitives on the following lines, see `sdf::parse_step`, and with
+    // the same material lines as objects.
+    pub fn parse_scene(source: &str, base_dir: &Path) -> Result<Self> {
+        let mut objects: Vec<Object> = Vec::new();
+        let mut shapes: Vec<Shape> = Vec::new();
+        let mut lights: Vec<Light> = Vec::new();
+        let mut last = Section::Object; // what the following lines apply to
+
+        for (line_nr, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut parts = line.split_whitespace();
+            let Some(keyword) = parts.next() else {
+                continue;
+            };
+            let args: Vec<&str> = parts.collect();
+            let context = || format!("line {}", line_nr + 1);
+
+            match keyword {
+                "light" => {
+                    let [name] = args.as_slice() else {
+                        return Err(eyre!("usage: light <name>")).wrap_err_with(context);
+                    };
+                    lights.push(Light::new(name));
+                    last = Section::Light;
+                }
+                "sdf" => {
+                    let [name] = args.as_slice() else {
+                        return Err(eyre!("usage: sdf <name>")).wrap_err_with(context);
+                    };
+                    shapes.push(Shape::new(name));
+                    last = Section::Shape;
+                }
+                "position" | "color" | "intensity" | "radius" if last == Section::Light => {
+                    let light = lights.last_mut().unwrap();
+                    parse_light_line(light, keyword, &args).wrap_err_with(context)?;
+                }
+                _ if last == Section::Light && keyword != "object" => {
+                    return Err(eyre!("'{}' doesn't apply to lights", keyword))
+                        .wrap_err_with(context)
+                }
+                "material" | "color" | "emissive" | "specular" | "shininess" | "wireframe"
+                | "opacity" | "metal" | "roughness" | "texture"
+                    if last == Section::Shape =>
+                {
+                    let shape = shapes.last_mut().unwrap();
+                    parse_material_line(&mut shape.material, keyword, &args, base_dir)
+                        .wrap_err_with(context)?;
+                }
+                "position" | "rotation" | "scale" if last == Section::Shape => {
+                    return Err(eyre!("'{}' doesn't apply to shapes", keyword))
+                        .wrap_err_with(context)
+                }
+                _ if last == Section::Shape && keyword != "object" => {
+                    let step = sdf::parse_step(keyword, &args).wrap_err_with(context)?;
+                    shapes.last_mut().unwrap().steps.push(step);
+                }
+                "object" => {
+                    last = Section::Object;
+                    let [name, mesh] = args.as_slice() else {
+                        return Err(eyre!("usage: object <name> <mesh>")).wrap_err_with(context);
+                    };
+                    let mesh = match Mesh::builtin(mesh) {
+                        Some(mesh) => mesh,
+                        None => Mesh::load_obj(base_dir.join(mesh)).wrap_err_with(context)?,
+                    };
+                    let mut object = Object::new(mesh);
+                    object.name = name.to_string();
+                    objects.push(object);
+                }
+                "material" | "color" | "emissive" | "specular" | "shininess" | "wireframe"
+                | "opacity" | "metal" | "roughness" | "texture" => {
+                    let Some(object) = objects.last_mut() else {
+                        return Err(eyre!("'{}' before any object", keyword))
+                            .wrap_err_with(context);
+                    };
+                    parse_material_line(&mut object.material, keyword, &args, base_dir)
+                        .wrap_err_with(context)?;
+                }
+                "position" | "rotation" | "scale" => {
+                    let Some(object) = objects.last_mut() else {
+                        return Err(eyre!("'{}' before any object", keyword))
+                            .wrap_err_with(context);
+                    };
+                    let value = parse_vec3(&args, keyword == "scale").wrap_err_with(context)?;
+                    match keyword {
+                        "position" => object.transform.position = value,
+                        "rotation" => object.transform.rotation = value,
+                        _ => object.transform.scale = value,
+                    }
+                }
+                _ => return Err(eyre!("unknown keyword '{}'", keyword)).wrap_err_with(context),
+            }
+        }
+
+        if objects.is_empty() && shapes.is_empty() {
+            return Err(eyre!("scene contains no objects"));
+        }
+        if let Some(shape) = shapes.iter().find(|shape| shape.steps.is_empty()) {
+            return Err(eyre!("shape {} has no primitives", shape.name));
+        }
+
+        Ok(Self {
+            objects,
+            shapes,
+            lights,
+        })
+    }
+
+    pub fn save_scene(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let base_dir = std::path::absolute(path)
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let source = self.to_scene_string(&base_dir)?;
+        std::fs::write(path, source).wrap_err_with(|| format!("could not write {}", path.display()))
+    }
+
+    // The inverse of `parse_scene`. Meshes loaded from OBJ files are referenced
+    // relative to `base_dir` when they are inside it.
+    pub fn to_scene_string(&self, base_dir: &Path) -> Result<String> {
+        let mut source = String::new();
+        for object in &self.objects {
+            let mesh = match &object.mesh.path {
+                Some(path) => path
+                    .strip_prefix(base_dir)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string(),
+                None if object.mesh.is_builtin() => object.mesh.name.clone(),
+                None => return Err(eyre!("the mesh of {} was never saved", object.name)),
+            };
+            let transform = &object.transform;
+            let vec3 = |v: Vec3| format!("{} {} {}", v.x, v.y, v.z);
+
+            if !source.is_empty() {
+                source.push('\n');
+            }
+            source += &format!("object {} {}\n", object.name, mesh);
+            source += &format!("position {}\n", vec3(transform.position));
+            source += &format!("rotation {}\n", vec3(transform.rotation));
+            source += &format!("scale {}\n", vec3(transform.scale));
+            let base = object.mesh.material.clone().unwrap_or_default();
+            source += &material_lines(&object.name, &object.material, &base, base_dir)?;
+        }
+        for shape in &self.shapes {
+            if !source.is_empty() {
+                source.push('\n');
+            }
+            source += &format!("sdf {}\n", shape.name);
+            for &(operation, primitive) in &shape.steps {
+                source += &sdf::format_step(operation, primitive);
+                source.push('\n');
+            }
+            source +=
+                &material_lines(&shape.name, &shape.material, &Material::default(), base_dir)?;
+        }
+        for light in &self.lights {
+            let position = light.position;
+            source += &format!("\nlight {}\n", light.name);
+            source += &format!("position {} {} {}\n", position.x, position.y, position.z);
+            source += &format!("color {}\n", format_color(light.color));
+            source += &format!("intensity {}\n", light.intensity);
+            source += &format!("radius {}\n", light.radius);
+        }
+        Ok(source)
+    }
+}
+
+// `material <library.mtl> <name>` uses a material of an MTL file, after which
+// `color <color>`, `emissive <color>`, `wireframe <color>`, `specular <0-1>`,
+// `shininess <n>`, `opacity <0-1>`, `metal <0-1>`, `roughness <0-1>` and
+// `texture <image> [nearest|bilinear] [repeat|clamp] [mipmaps]` change it.
+fn parse_material_line(
+    material: &mut Material,
+    keyword: &str,
+    args: &[&str],
+    base_dir: &Path,
+) -> Result<()> {
+    let color = || parse_color(keyword, args);
+    let number = |range| parse_number(keyword, args, range);
+
+    match keyword {
+        "material" => {
+            let [library, name] = args else {
+                return Err(eyre!("usage: material <library.mtl> <name>"));
+            };
+            *material = Material::load_library(base_dir.join(library))?
+                .into_iter()
+                .find(|material| material.name == *name)
+                .ok_or_else(|| eyre!("material '{}' not found in {}", name, library))?;
+        }
+        "color" => material.color = color()?,
+        "emissive" => material.emissive = color()?,
+        "wireframe" => material.wireframe = Some(color()?),
+        "specular" => material.specular = number(0.0..=1.0)?,
+        "shininess" => material.shininess = number(0.0..=f64::MAX)?,
+        "opacity" => material.opacity = number(0.0..=1.0)?,
+        "metal" => material.metal = number(0.0..=1.0)?,
+        "roughness" => material.roughness = number(0.0..=1.0)?,
+        _ => material.texture = Some(Arc::new(parse_texture(args, base_dir)?)),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Object,
+    Light,
+    Shape,
+}
+
+fn parse_light_line(light: &mut Light, keyword: &str, args: &[&str]) -> Result<()> {
+    match keyword {
+        "position" => light.position = parse_vec3(args, false)?,
+        "color" => light.color = parse_color(keyword, args)?,
+        "intensity" => light.intensity = parse_number(keyword, args, 0.0..=f64::MAX)?,
+        _ => light.radius = parse_number(keyword, args, 0.0..=f64::MAX)?,
+    }
+    Ok(())
+}
+
+// Colors are names, indices or red, green and blue from 0 to 255, as # starts
+// a comment
+fn parse_color(keyword: &str, args: &[&str]) -> Result<Color> {
+    match *args {
+        [r, g, b] => {
+            let channel = |value: &str| {
+                value
+                    .parse::<u8>()
+                    .map_err(|_| eyre!("invalid color channel '{}'", value))
+            };
+            Ok(Color::Rgb(channel(r)?, channel(g)?, channel(b)?))
+        }
+        [color] if !color.starts_with('#') => color
+            .parse()
+            .map_err(|_| eyre!("invalid color '{}'", color)),
+        _ => Err(eyre!("usage: {} <name|index|r g b>", keyword)),
+    }
+}
+
+fn format_color(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("{} {} {}", r, g, b),
+        color => color.to_string().to_lowercase(),
+    }
+}
+
+fn parse_number(keyword: &str, args: &[&str], range: std::ops::RangeInclusive<f64>) -> Result<f64> {
+    let [number] = args else {
+        return Err(eyre!("usage: {} <number>", keyword));
+    };
+    number
+        .parse()
+        .ok()
+        .filter(|number| range.contains(number))
+        .ok_or_else(|| eyre!("{} must be a number in {:?}", keyword, range))
+}
+
+// The lines that turn the base material of an object or shape into its
+// current one
+fn material_lines(
+    name: &str,
+    material: &Material,
+    base: &Material,
+    base_dir: &Path,
+) -> Result<String> {
+    let color = format_color;
+
+    let mut lines = String::new();
+    if material.color != base.color {
+        lines += &format!("color {}\n", color(material.color));
+    }
+    if material.emissive != base.emissive {
+        lines += &format!("emissive {}\n", color(material.emissive));
+    }
+    if let Some(wireframe) = material
+        .wireframe
+        .filter(|_| material.wireframe != base.wireframe)
+    {
+        lines += &format!("wireframe {}\n", color(wireframe));
+    }
+    if material.specular != base.specular {
+        lines += &format!("specular {}\n", material.specular);
+    }
+    if material.shininess != base.shininess {
+        lines += &format!("shininess {}\n", material.shininess);
+    }
+    if material.opacity != base.opacity {
+        lines += &format!("opacity {}\n", material.opacity);
+    }
+    if material.metal != base.metal {
+        lines += &format!("metal {}\n", material.metal);
+    }
+    if material.roughness != base.roughness {
+        lines += &format!("roughness {}\n", material.roughness);
+    }
+
+    let unchanged = match (&material.texture, &base.texture) {
+        (Some(texture), Some(base)) => Arc::ptr_eq(texture, base),
+        (texture, _) => texture.is_none(),
+    };
+    if let Some(texture) = material.texture.as_ref().filter(|_| !unchanged) {
+        let Some(path) = &texture.path else {
+            return Err(eyre!("the texture of {} has no file", name));
+        };
+        let filter = match texture.filter {
+            Filter::Nearest => "nearest",
+            Filter::Bilinear => "bilinear",
+        };
+        let wrap = match texture.wrap {
+            Wrap::Repeat => "repeat",
+            Wrap::Clamp => "clamp",
+        };
+        lines += &format!(
+            "texture {} {} {}{}\n",
+            path.strip_prefix(base_dir).unwrap_or(path).display(),
+            filter,
+            wrap,
+            if texture.has_mipmaps() {
+                " mipmaps"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(lines)
+}
+
+fn parse_texture(args: &[&str], base_dir: &Path) -> Result<Texture> {
+    let Some((path, options)) = args.split_first() else {
+        return Err(eyre!(
+            "usage: texture <image> [nearest|bilinear] [repeat|clamp] [mipmaps]"
+        ));
+    };
+    let mut texture = Texture::load(base_dir.join(path))?;
+    for &option in options {
+        match option {
+            "nearest" => texture.filter = Filter::Nearest,
+            "bilinear" => texture.filter = Filter::Bilinear,
+            "repeat" => texture.wrap = Wrap::Repeat,
+            "clamp" => texture.wrap = Wrap::Clamp,
+            "mipmaps" => texture = texture.with_mipmaps(),
+            _ => return Err(eyre!("unknown texture option '{}'", option)),
+        }
+    }
+    Ok(texture)
+}
+
+// A single value is accepted for uniform scaling
+fn parse_vec3(args: &[&str], allow_uniform: bool) -> Result<Vec3> {
+    let values = args
+        .iter()
+        .map(|arg| arg.parse::<f64>().ok().filter(|value| value.is_finite()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| eyre!("invalid number"))?;
+
+    match *values.as_slice() {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        [v] if allow_uniform => Ok(Vec3::new(v, v, v)),
+        _ => Err(eyre!("expected 3 numbers, got {}", values.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scene_string_round_trips() {
+        let source = "object cube cube\nscale 3\n\nobject prism prism\nposition 1.5 0 -0.25\nrotation 0 30 0\n";
+        let scene = Scene::parse_scene(source, Path::new(".")).unwrap();
+        let saved = scene.to_scene_string(Path::new(".")).unwrap();
+        let loaded = Scene::parse_scene(&saved, Path::new(".")).unwrap();
+
+        assert_eq!(loaded.objects.len(), 2);
+        for (a, b) in scene.objects.iter().zip(&loaded.objects) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.mesh.name, b.mesh.name);
+            assert_eq!(format!("{:?}", a.transform), format!("{:?}", b.transform));
+        }
+    }
+
+    #[test]
+    fn materials_round_trip() {
+        let source =
+            "object glass cube\ncolor 51 102 255\nspecular 0.5\nshininess 64\nopacity 0.25\n\
+                      \nobject lamp pyramid\nemissive yellow\nwireframe white\nmetal 1\nroughness 0.5\n";
+        let scene = Scene::parse_scene(source, Path::new(".")).unwrap();
+        let [glass, lamp] = [&scene.objects[0].material, &scene.objects[1].material];
+        assert_eq!(glass.color, Color::Rgb(51, 102, 255));
+        assert_eq!(
+            (glass.specular, glass.shininess, glass.opacity),
+            (0.5, 64.0, 0.25)
+        );
+        assert_eq!(
+            (lamp.emissive, lamp.edge_color()),
+            (Color::Yellow, Color::White)
+        );
+        assert_eq!((lamp.metal, lamp.roughness), (1.0, 0.5));
+
+        let saved = scene.to_scene_string(Path::new(".")).unwrap();
+        assert!(!saved.contains("emissive black"), "{}", saved);
+        let loaded = Scene::parse_scene(&saved, Path::new(".")).unwrap();
+        for (a, b) in scene.objects.iter().zip(&loaded.objects) {
+            assert_eq!(format!("{:?}", a.material), format!("{:?}", b.material));
+        }
+        assert!(Scene::parse_scene("object cube cube\nopacity 2\n", Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn lights_round_trip() {
+        let source =
+            "object cube cube\nlight sun\nposition 1 2 -3\ncolor 255 240 200\nradius 0.5\n\
+                      \nlight fill\nintensity 0.3\n";
+        let scene = Scene::parse_scene(source, Path::new(".")).unwrap();
+        let [sun, fill] = scene.lights.as_slice() else {
+            panic!("{:?}", scene.lights);
+        };
+        assert_eq!(sun.color, Color::Rgb(255, 240, 200));
+        assert_eq!((sun.radius, fill.intensity), (0.5, 0.3));
+
+        let saved = scene.to_scene_string(Path::new(".")).unwrap();
+        let loaded = Scene::parse_scene(&saved, Path::new(".")).unwrap();
+        assert_eq!(
+            format!("{:?}", loaded.lights),
+            format!("{:?}", scene.lights)
+        );
+        // Lights have no materials
+        assert!(
+            Scene::parse_scene("object cube cube\nlight sun\nopacity 1\n", Path::new(".")).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers() {
+        for line in [
+            "position nan 0 0",
+            "rotation 0 -inf 0",
+            "scale inf",
+            "opacity nan",
+            "shininess inf",
+        ] {
+            let source = format!("object cube cube\n{}\n", line);
+            assert!(
+                Scene::parse_scene(&source, Path::new(".")).is_err(),
+                "{}",
+                line
+            );
+        }
+        for line in ["position 0 nan 0", "radius inf", "intensity nan"] {
+            let source = format!("object cube cube\nlight sun\n{}\n", line);
+            assert!(
+                Scene::parse_scene(&source, Path::new(".")).is_err(),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn shapes_round_trip() {
+        let source = "sdf blob\nsphere 0 0 0 0.5\nsmooth 0.2 box 0.5 0 0 0.25 0.25 0.25\n\
+                      subtract torus 0 0.5 0 0.3 0.1\ncolor 0 128 255\nspecular 0.5\n\
+                      \nsdf ground\nplane 0 1 0 -1\n";
+        let scene = Scene::parse_scene(source, Path::new(".")).unwrap();
+        let [blob, ground] = scene.shapes.as_slice() else {
+            panic!("{:?}", scene.shapes);
+        };
+        assert!(scene.objects.is_empty());
+        assert_eq!(blob.steps.len(), 3);
+        assert_eq!(blob.material.color, Color::Rgb(0, 128, 255));
+        assert_eq!(ground.material.color, Color::Red);
+
+        let saved = scene.to_scene_string(Path::new(".")).unwrap();
+        let loaded = Scene::parse_scene(&saved, Path::new(".")).unwrap();
+        assert_eq!(
+            format!("{:?}", loaded.shapes),
+            format!("{:?}", scene.shapes)
+        );
+        for source in [
+            "sdf blob\n",
+            "sdf blob\nsphere 0 0 0\n",
+            "sdf blob\nscale 2\n",
+        ] {
+            assert!(
+                Scene::parse_scene(source, Path::new(".")).is_err(),
+                "{}",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use crate::material::Material;
+use crate::types::Vec3;
+use color_eyre::eyre::{eyre, Result};
+
+// Signed distance fields, negative inside the surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+    },
+    Box {
+        center: Vec3,
+        half_size: Vec3,
+    },
+    // Lying flat, around the y axis
+    Torus {
+        center: Vec3,
+        major: f64,
+        minor: f64,
+    },
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f64,
+    },
+    // The points whose dot with the normal is the offset
+    Plane {
+        normal: Vec3,
+        offset: f64,
+    },
+}
+impl Primitive {
+    pub fn distance(&self, p: Vec3) -> f64 {
+        match *self {
+            Self::Sphere { center, radius } => (p - center).length() - radius,
+            Self::Box { center, half_size } => {
+                let d = p - center;
+                let q = Vec3::new(
+                    d.x.abs() - half_size.x,
+                    d.y.abs() - half_size.y,
+                    d.z.abs() - half_size.z,
+                );
+                let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+                outside + q.x.max(q.y).max(q.z).min(0.0)
+            }
+            Self::Torus {
+                center,
+                major,
+                minor,
+            } => {
+                let d = p - center;
+                (d.x.hypot(d.z) - major).hypot(d.y) - minor
+            }
+            Self::Capsule { a, b, radius } => {
+                let (pa, ba) = (p - a, b - a);
+                let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+                let h = if h.is_nan() { 0.0 } else { h };
+                (pa - ba * h).length() - radius
+            }
+            Self::Plane { normal, offset } => p.dot(normal) - offset,
+        }
+    }
+}
+
+// How a primitive is combined with the ones before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Union,
+    Subtract,
+    Intersect,
+    SmoothUnion(f64), // blends the surfaces within this distance of each other
+}
+impl Operation {
+    pub fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Self::Union => a.min(b),
+            Self::Subtract => a.max(-b),
+            Self::Intersect => a.max(b),
+            Self::SmoothUnion(k) => {
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+                b + (a - b) * h - k * h * (1.0 - h)
+            }
+        }
+    }
+}
+
+// Primitives combined in order, the operation of the first one is ignored
+#[derive(Debug, Clone)]
+pub struct Shape {
+    pub name: String,
+    pub steps: Vec<(Operation, Primitive)>,
+    pub material: Material,
+}
+impl Shape {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            steps: Vec::new(),
+            material: Material::default(),
+        }
+    }
+
+    pub fn distance(&self, p: Vec3) -> f64 {
+        let mut steps = self.steps.iter();
+        let Some((_, first)) = steps.next() else {
+            return f64::INFINITY;
+        };
+        steps.fold(first.distance(p), |distance, (operation, primitive)| {
+            operation.apply(distance, primitive.distance(p))
+        })
+    }
+
+    // The gradient of the distance, from four samples around the point
+    pub fn normal(&self, p: Vec3) -> Vec3 {
+        const E: f64 = 1e-5;
+        [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ]
+        .into_iter()
+        .fold(Vec3::new(0.0, 0.0, 0.0), |sum, k| {
+            sum + k * self.distance(p + k * E)
+        })
+        .normalize()
+    }
+}
+
+// Closer than this counts as on the surface
+pub const HIT_DISTANCE: f64 = 1e-4;
+const MAX_STEPS: usize = 256;
+
+// The distance to the nearest shape, and its index
+pub fn distance(shapes: &[Shape], p: Vec3) -> Option<(f64, usize)> {
+    shapes
+        .iter()
+        .enumerate()
+        .map(|(index, shape)| (shape.distance(p), index))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+// Steps along the ray by the distance to the nearest surface, which can't be
+// overshot, until it is close enough to one or further than `max_t`
+pub fn march(shapes: &[Shape], origin: Vec3, ray: Vec3, max_t: f64) -> Option<(f64, usize)> {
+    let mut t = 0.0;
+    for _ in 0..MAX_STEPS {
+        let (d, shape) = distance(shapes, origin + ray * t)?;
+        if d < HIT_DISTANCE * (1.0 + t) {
+            return Some((t, shape));
+        }
+        t += d;
+        if t > max_t {
+            break;
+        }
+    }
+    None
+}
+
+// 1 in the open, less in creases and near other surfaces, by comparing the
+// distance to the surfaces with the distance from the point along the normal
+pub fn ambient_occlusion(shapes: &[Shape], p: Vec3, normal: Vec3) -> f64 {
+    let mut occlusion = 0.0;
+    let mut weight = 1.0;
+    for i in 1..=5 {
+        let h = 0.03 * i as f64;
+        let d = distance(shapes, p + normal * h).map_or(h, |(d, _)| d);
+        occlusion += (h - d).max(0.0) * weight;
+        weight *= 0.7;
+    }
+    (1.0 - 3.0 * occlusion).clamp(0.0, 1.0)
+}
+
+// How much of a light at `to` reaches `from`. With a radius, rays that pass
+// close to a surface are partly shadowed, which softens the edges of shadows
+// like a light of that size would.
+pub fn shadow(shapes: &[Shape], from: Vec3, to: Vec3, radius: Option<f64>) -> f64 {
+    let length = (to - from).length();
+    let ray = (to - from).normalize();
+    let mut light: f64 = 1.0;
+    let mut t = 1e-3;
+    for _ in 0..MAX_STEPS {
+        if t >= length {
+            break;
+        }
+        let Some((d, _)) = distance(shapes, from + ray * t) else {
+            break;
+        };
+        if d < HIT_DISTANCE {
+            return 0.0;
+        }
+        if let Some(radius) = radius.filter(|&radius| radius > 0.0) {
+            // The fraction of the light's apparent size the surface misses by
+            light = light.min(d * length / (t * radius));
+        }
+        t += d;
+    }
+    light.clamp(0.0, 1.0)
+}
+
+// `sphere x y z r`, `box x y z hx hy hz` with half sizes, `torus x y z major
+// minor`, `capsule x1 y1 z1 x2 y2 z2 r` and `plane nx ny nz offset`, after
+// `subtract`, `intersect` or `smooth <k>` to combine them other than by union
+pub fn parse_step(keyword: &str, args: &[&str]) -> Result<(Operation, Primitive)> {
+    let (operation, keyword, args) = match keyword {
+        "union" | "subtract" | "intersect" | "smooth" => {
+            let operation = match keyword {
+                "union" => Operation::Union,
+                "subtract" => Operation::Subtract,
+                "intersect" => Operation::Intersect,
+                _ => {
+                    let k = args
+                        .first()
+                        .and_then(|k| k.parse::<f64>().ok())
+                        .filter(|&k| k.is_finite() && k > 0.0)
+                        .ok_or_else(|| eyre!("usage: smooth <k> <primitive>"))?;
+                    Operation::SmoothUnion(k)
+                }
+            };
+            let args = if keyword == "smooth" {
+                &args[1..]
+            } else {
+                args
+            };
+            let Some((primitive, args)) = args.split_first() else {
+                return Err(eyre!("{} needs a primitive", keyword));
+            };
+            (operation, *primitive, args)
+        }
+        _ => (Operation::Union, keyword, args),
+    };
+
+    let values = args
+        .iter()
+        .map(|arg| arg.parse::<f64>().ok().filter(|value| value.is_finite()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| eyre!("invalid number"))?;
+    let primitive = match (keyword, values.as_slice()) {
+        ("sphere", &[x, y, z, radius]) => Primitive::Sphere {
+            center: Vec3::new(x, y, z),
+            radius,
+        },
+        ("box", &[x, y, z, hx, hy, hz]) => Primitive::Box {
+            center: Vec3::new(x, y, z),
+            half_size: Vec3::new(hx, hy, hz),
+        },
+        ("torus", &[x, y, z, major, minor]) => Primitive::Torus {
+            center: Vec3::new(x, y, z),
+            major,
+            minor,
+        },
+        ("capsule", &[x1, y1, z1, x2, y2, z2, radius]) => Primitive::Capsule {
+            a: Vec3::new(x1, y1, z1),
+            b: Vec3::new(x2, y2, z2),
+            radius,
+        },
+        ("plane", &[nx, ny, nz, offset]) => {
+            let normal = Vec3::new(nx, ny, nz);
+            if normal.length() == 0.0 {
+                return Err(eyre!("the normal of a plane can't be zero"));
+            }
+            Primitive::Plane {
+                normal: normal.normalize(),
+                offset,
+            }
+        }
+        ("sphere", _) => return Err(eyre!("usage: sphere <x y z> <radius>")),
+        ("box", _) => return Err(eyre!("usage: box <x y z> <half x y z>")),
+        ("torus", _) => return Err(eyre!("usage: torus <x y z> <major> <minor>")),
+        ("capsule", _) => return Err(eyre!("usage: capsule <x y z> <x y z> <radius>")),
+        ("plane", _) => return Err(eyre!("usage: plane <normal x y z> <offset>")),
+        _ => return Err(eyre!("unknown primitive '{}'", keyword)),
+    };
+    let sizes = match primitive {
+        Primitive::Sphere { radius, .. } | Primitive::Capsule { radius, .. } => vec![radius],
+        Primitive::Box { half_size, .. } => vec![half_size.x, half_size.y, half_size.z],
+        Primitive::Torus { major, minor, .. } => vec![major, minor],
+        Primitive::Plane { .. } => Vec::new(),
+    };
+    if sizes.iter().any(|&size| size < 0.0) {
+        return Err(eyre!("the size of a {} can't be negative", keyword));
+    }
+    Ok((operation, primitive))
+}
+
+// The inverse of `parse_step`
+pub fn format_step(operation: Operation, primitive: Primitive) -> String {
+    let operation = match operation {
+        Operation::Union => String::new(),
+        Operation::Subtract => String::from("subtract "),
+        Operation::Intersect => String::from("intersect "),
+        Operation::SmoothUnion(k) => format!("smooth {} ", k),
+    };
+    let vec3 = |v: Vec3| format!("{} {} {}", v.x, v.y, v.z);
+    let primitive = match primitive {
+        Primitive::Sphere { center, radius } => format!("sphere {} {}", vec3(center), radius),
+        Primitive::Box { center, half_size } => {
+            format!("box {} {}", vec3(center), vec3(half_size))
+        }
+        Primitive::Torus {
+            center,
+            major,
+            minor,
+        } => format!("torus {} {} {}", vec3(center), major, minor),
+        Primitive::Capsule { a, b, radius } => {
+            format!("capsule {} {} {}", vec3(a), vec3(b), radius)
+        }
+        Primitive::Plane { normal, offset } => format!("plane {} {}", vec3(normal), offset),
+    };
+    operation + &primitive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(lines: &[&str]) -> Shape {
+        let mut shape = Shape::new("test");
+        for line in lines {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            shape.steps.push(parse_step(parts[0], &parts[1..]).unwrap());
+        }
+        shape
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let distance = |line| shape(&[line]).distance(origin);
+        assert_eq!(distance("sphere 2 0 0 0.5"), 1.5);
+        assert_eq!(distance("box 0 3 0 1 1 1"), 2.0);
+        assert_eq!(distance("box 0 0 0 1 2 3"), -1.0);
+        assert_eq!(distance("torus 0 0 0 1 0.25"), 0.75);
+        assert_eq!(distance("capsule -1 2 0 1 2 0 0.5"), 1.5);
+        assert_eq!(distance("plane 0 2 0 -1"), 1.0);
+        assert!(parse_step("sphere", &["1", "2"]).is_err());
+        assert!(parse_step("cone", &[]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_numbers_and_negative_sizes() {
+        for line in [
+            "sphere nan 0 0 1",
+            "sphere 0 0 0 inf",
+            "sphere 0 0 0 -1",
+            "box 0 0 0 1 -1 1",
+            "torus 0 0 0 1 -0.25",
+            "capsule 0 0 0 0 1 0 -0.5",
+            "plane 0 1 0 -inf",
+            "smooth inf sphere 0 0 0 1",
+            "smooth 0 sphere 0 0 0 1",
+        ] {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            assert!(parse_step(parts[0], &parts[1..]).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn operations_combine_in_order() {
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        // A ball with a hole through the middle
+        let hollow = shape(&["sphere 0 0 0 1", "subtract capsule 0 -2 0 0 2 0 0.25"]);
+        assert_eq!(hollow.distance(p), 0.25);
+        assert!(hollow.distance(Vec3::new(0.5, 0.0, 0.0)) < 0.0);
+
+        let lens = shape(&["sphere -0.5 0 0 1", "intersect sphere 0.5 0 0 1"]);
+        assert_eq!(lens.distance(Vec3::new(0.0, 0.0, 0.0)), -0.5);
+        assert_eq!(lens.distance(Vec3::new(1.0, 0.0, 0.0)), 0.5);
+
+        // Smoothing bulges out between the spheres, not far from them
+        let hard = shape(&["sphere -1 0 0 0.5", "sphere 1 0 0 0.5"]);
+        let smooth = shape(&["sphere -1 0 0 0.5", "smooth 0.5 sphere 1 0 0 0.5"]);
+        assert!(smooth.distance(p) < hard.distance(p));
+        let far = Vec3::new(-3.0, 0.0, 0.0);
+        assert_eq!(smooth.distance(far), hard.distance(far));
+
+        for (operation, primitive) in &smooth.steps {
+            let line = format_step(*operation, *primitive);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(
+                parse_step(parts[0], &parts[1..]).unwrap(),
+                (*operation, *primitive)
+            );
+        }
+    }
+
+    #[test]
+    fn marches_to_surfaces_with_normals_and_occlusion() {
+        let shapes = [shape(&["sphere 0 0 3 1"]), shape(&["plane 0 1 0 -1"])];
+        let forward = Vec3::new(0.0, 0.0, 1.0);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let (t, index) = march(&shapes, origin, forward, 100.0).unwrap();
+        assert!((t - 2.0).abs() < 1e-3, "{}", t);
+        assert_eq!(index, 0);
+        let normal = shapes[0].normal(origin + forward * t);
+        assert!((normal.z + 1.0).abs() < 1e-3, "{:?}", normal);
+        assert!(march(&shapes, origin, Vec3::new(0.0, 1.0, 0.0), 100.0).is_none());
+
+        // The ground under the sphere is in its shadow and darker where they touch
+        let ground = Vec3::new(0.0, -1.0, 3.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let light = Vec3::new(0.0, 5.0, 3.0);
+        assert_eq!(shadow(&shapes, ground, light, None), 0.0);
+        assert_eq!(shadow(&shapes, Vec3::new(5.0, -1.0, 3.0), light, None), 1.0);
+        let open = ambient_occlusion(&shapes, Vec3::new(5.0, -1.0, 3.0), up);
+        let corner = ambient_occlusion(
+            &[shape(&["plane 0 1 0 -1", "box 0 0 0 1 1 1"])],
+            Vec3::new(1.0, -1.0, 0.0),
+            up,
+        );
+        assert_eq!(open, 1.0);
+        assert!(corner < open);
+    }
+}
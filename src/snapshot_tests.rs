@@ -0,0 +1,360 @@
+// Renders known scenes from fixed cameras and compares them against the
+// golden buffers in tests/golden. Run with UPDATE_GOLDEN=1 to rewrite them
+// after an intended change in the output.
+use crate::camera::{Camera, ViewPreset};
+use crate::color::{ColorSupport, Dither};
+use crate::graphics::{GraphicsBackend, GraphicsProtocol};
+use crate::output::DrawMode;
+use crate::render::HiddenLines;
+use crate::scene::Scene;
+use crate::types::Vec3;
+use crate::viewport::{RenderMode, Viewport3D, ViewportState};
+
+use ratatui::{
+    backend::TestBackend, buffer::Buffer, layout::Rect, style::Color, widgets::StatefulWidget,
+    Terminal,
+};
+use std::path::PathBuf;
+
+struct Setup {
+    scene: Scene,
+    camera: Camera,
+    marker: DrawMode,
+}
+
+fn setup(scene: Scene, pos: Vec3, yaw: f64, pitch: f64, marker: DrawMode) -> Setup {
+    Setup {
+        scene,
+        camera: Camera {
+            pos,
+            yaw,
+            pitch,
+            ..Default::default()
+        },
+        marker,
+    }
+}
+
+fn render(setup: Setup, width: u16, height: u16) -> String {
+    render_mode(setup, width, height, RenderMode::Vertex)
+}
+
+fn render_mode(setup: Setup, width: u16, height: u16, render_mode: RenderMode) -> String {
+    let viewport = Viewport3D::new(&setup.scene)
+        .draw_mode(setup.marker)
+        .render_mode(render_mode)
+        .background(Color::Blue);
+    render_viewport(viewport, setup.camera, width, height)
+}
+
+fn render_viewport(viewport: Viewport3D, camera: Camera, width: u16, height: u16) -> String {
+    let mut state = ViewportState::new(camera);
+
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal
+        .draw(|frame| frame.render_stateful_widget(viewport, frame.area(), &mut state))
+        .unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let mut lines = String::new();
+    for y in 0..buffer.area.height {
+        for x in 0..buffer.area.width {
+            lines.push_str(buffer[(x, y)].symbol());
+        }
+        lines.push('\n');
+    }
+    lines
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    assert_fixture(&format!("{}.txt", name), actual);
+}
+
+fn assert_fixture(file_name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(file_name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing golden file {}", path.display()));
+    assert!(
+        expected == actual,
+        "{} differs from the golden buffer\n--- expected\n{}--- actual\n{}",
+        file_name,
+        expected,
+        actual
+    );
+}
+
+fn shapes_scene() -> Scene {
+    Scene::load_scene(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/shapes.scene")).unwrap()
+}
+
+#[test]
+fn cube_front_braille() {
+    let setup = setup(
+        Scene::default(),
+        Vec3::new(0.0, 0.0, -1.0),
+        0.0,
+        0.0,
+        DrawMode::Braille,
+    );
+    assert_golden("cube_front_braille", &render(setup, 40, 20));
+}
+
+#[test]
+fn cube_corner_halfblock() {
+    let setup = setup(
+        Scene::default(),
+        Vec3::new(-0.6, 0.4, -0.6),
+        45.0,
+        -25.0,
+        DrawMode::HalfBlock,
+    );
+    assert_golden("cube_corner_halfblock", &render(setup, 40, 20));
+}
+
+#[test]
+fn shapes_scene_block() {
+    let setup = setup(
+        shapes_scene(),
+        Vec3::new(0.0, 0.5, -2.0),
+        10.0,
+        -5.0,
+        DrawMode::Block,
+    );
+    assert_golden("shapes_scene_block", &render(setup, 60, 20));
+}
+
+#[test]
+fn inside_cube_clips_edges() {
+    // Edges pass behind the camera and have to be clipped at the near plane
+    let mut scene = Scene::default();
+    scene.objects[0].transform.scale = Vec3::new(10.0, 10.0, 10.0);
+    let mut setup = setup(
+        scene,
+        Vec3::new(0.0, 0.0, 0.0),
+        30.0,
+        10.0,
+        DrawMode::Braille,
+    );
+    setup.camera.fov = 120.0;
+    assert_golden("inside_cube_clips_edges", &render(setup, 40, 20));
+}
+
+#[test]
+fn cube_corner_ascii() {
+    let setup = setup(
+        Scene::default(),
+        Vec3::new(-0.6, 0.4, -0.6),
+        45.0,
+        -25.0,
+        DrawMode::Ascii,
+    );
+    assert_golden("cube_corner_ascii", &render(setup, 40, 20));
+}
+
+#[test]
+fn shapes_scene_hidden_lines() {
+    // The far edges of the cube and the shapes behind it are dashed
+    let setup = setup(
+        shapes_scene(),
+        Vec3::new(1.2, 1.2, -2.5),
+        -25.0,
+        -20.0,
+        DrawMode::Braille,
+    );
+    let viewport = Viewport3D::new(&setup.scene)
+        .draw_mode(setup.marker)
+        .hidden_lines(HiddenLines::Dashed)
+        .background(Color::Blue);
+    assert_golden(
+        "shapes_scene_hidden_lines",
+        &render_viewport(viewport, setup.camera, 60, 22),
+    );
+}
+
+#[test]
+fn cube_isometric() {
+    // The edges along every axis come out the same length, the front and back
+    // corners on top of each other in the middle
+    let mut camera = Camera {
+        aspect_ratio: 1.0,
+        extent: 0.2,
+        ..Default::default()
+    };
+    ViewPreset::Isometric.apply(&mut camera, Vec3::new(0.0, 0.0, 0.0), 5.0);
+    let scene = Scene::default();
+    let viewport = Viewport3D::new(&scene)
+        .draw_mode(DrawMode::Braille)
+        .background(Color::Blue);
+    assert_golden("cube_isometric", &render_viewport(viewport, camera, 40, 20));
+}
+
+#[test]
+fn cube_corner_raycast() {
+    let setup = setup(
+        Scene::default(),
+        Vec3::new(-0.6, 0.4, -0.6),
+        45.0,
+        -25.0,
+        DrawMode::HalfBlock,
+    );
+    assert_golden(
+        "cube_corner_raycast",
+        &render_mode(setup, 40, 20, RenderMode::Raycast),
+    );
+}
+
+#[test]
+fn pixels_color_both_halves() {
+    let setup = setup(
+        Scene::default(),
+        Vec3::new(0.0, 0.0, -1.0),
+        0.0,
+        0.0,
+        DrawMode::Pixels,
+    );
+    let viewport = Viewport3D::new(&setup.scene)
+        .draw_mode(setup.marker)
+        .render_mode(RenderMode::Raycast)
+        .background(Color::Blue);
+    let mut state = ViewportState::new(setup.camera);
+    let area = Rect::new(0, 0, 40, 20);
+    let mut buf = Buffer::empty(area);
+    viewport.render(area, &mut buf, &mut state);
+
+    // The face fills the center, the corner is background
+    let center = &buf[(20, 10)];
+    assert_eq!(center.symbol(), "▀");
+    assert!(matches!(center.fg, Color::Rgb(..)) && matches!(center.bg, Color::Rgb(..)));
+    assert_eq!((buf[(0, 0)].fg, buf[(0, 0)].bg), (Color::Blue, Color::Blue));
+}
+
+#[test]
+fn cube_corner_monochrome_dither() {
+    let setup = setup(
+        Scene::default(),
+        Vec3::new(-0.6, 0.4, -0.6),
+        45.0,
+        -25.0,
+        DrawMode::Braille,
+    );
+    let viewport = Viewport3D::new(&setup.scene)
+        .draw_mode(setup.marker)
+        .render_mode(RenderMode::Raycast)
+        .color_support(ColorSupport::Monochrome)
+        .dither(Dither::Ordered);
+    assert_golden(
+        "cube_corner_monochrome_dither",
+        &render_viewport(viewport, setup.camera, 40, 20),
+    );
+}
+
+fn graphics_stream(protocol: GraphicsProtocol) -> String {
+    let setup = setup(
+        Scene::default(),
+        Vec3::new(-0.6, 0.4, -0.6),
+        45.0,
+        -25.0,
+        DrawMode::Graphics,
+    );
+    let backend = GraphicsBackend {
+        protocol,
+        cell_width: 4,
+        cell_height: 8,
+    };
+    let viewport = Viewport3D::new(&setup.scene)
+        .draw_mode(setup.marker)
+        .backend(&backend)
+        .render_mode(RenderMode::Raycast);
+    let mut state = ViewportState::new(setup.camera);
+    let area = Rect::new(0, 0, 12, 6);
+    let mut buf = Buffer::empty(area);
+    viewport.render(area, &mut buf, &mut state);
+
+    // Everything but the cell holding the image is left alone
+    assert!(area.positions().skip(1).all(|position| buf[position].skip));
+    buf[(0, 0)].symbol().to_string()
+}
+
+#[test]
+fn cube_corner_sixel() {
+    assert_fixture(
+        "cube_corner.sixel",
+        &graphics_stream(GraphicsProtocol::Sixel),
+    );
+}
+
+#[test]
+fn cube_corner_kitty() {
+    assert_fixture(
+        "cube_corner.kitty",
+        &graphics_stream(GraphicsProtocol::Kitty),
+    );
+}
+
+#[test]
+fn square_stays_square_in_every_draw_mode() {
+    for cell_aspect in [1.8, 2.0, 2.25] {
+        for draw_mode in [
+            DrawMode::Braille,
+            DrawMode::HalfBlock,
+            DrawMode::Block,
+            DrawMode::Ascii,
+            DrawMode::Pixels,
+        ] {
+            let setup = setup(
+                Scene::default(),
+                Vec3::new(0.0, 0.0, -1.5),
+                0.0,
+                0.0,
+                draw_mode,
+            );
+            let viewport = Viewport3D::new(&setup.scene)
+                .draw_mode(draw_mode)
+                .render_mode(RenderMode::Raycast)
+                .cell_aspect(cell_aspect);
+            let mut state = ViewportState::new(setup.camera);
+            let area = Rect::new(0, 0, 160, 60);
+            let mut buf = Buffer::empty(area);
+            viewport.render(area, &mut buf, &mut state);
+
+            // Cells that show part of the cube
+            let drawn: Vec<_> = area
+                .positions()
+                .filter(|&position| {
+                    let cell = &buf[position];
+                    match draw_mode {
+                        DrawMode::Pixels => cell.fg != Color::Reset || cell.bg != Color::Reset,
+                        _ => cell.symbol() != " ",
+                    }
+                })
+                .collect();
+            let columns = drawn.iter().map(|p| p.x).max().unwrap()
+                - drawn.iter().map(|p| p.x).min().unwrap()
+                + 1;
+            let rows = drawn.iter().map(|p| p.y).max().unwrap()
+                - drawn.iter().map(|p| p.y).min().unwrap()
+                + 1;
+            // Partly covered cells at the edges add up to two cells
+            let (rows, columns) = (rows as f64, columns as f64);
+            let min_ratio = (rows - 2.0) * cell_aspect / columns;
+            let max_ratio = rows * cell_aspect / (columns - 2.0);
+            assert!(
+                (min_ratio..=max_ratio).contains(&1.0),
+                "{:?} with cells of {}: {} columns, {} rows",
+                draw_mode,
+                cell_aspect,
+                columns,
+                rows
+            );
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::camera::Camera;
+use crate::color;
+use crate::framebuffer::Framebuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoMode {
+    Off,
+    // Left eye in red, right eye in cyan, for red/cyan glasses
+    Anaglyph,
+    // Left eye on the left half, for looking at it parallel
+    SideBySide,
+    // Left eye on the right half, for looking at it cross-eyed
+    CrossEyed,
+}
+
+// Renders the scene from two cameras next to each other, both turned in so
+// that they see the same point at the convergence distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stereo {
+    pub mode: StereoMode,
+    pub eye_distance: f64, // between the cameras, along `Camera::right()`
+    pub convergence: f64,  // in front of the camera
+}
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            mode: StereoMode::Off,
+            eye_distance: 0.06,
+            convergence: 1.0,
+        }
+    }
+}
+impl Stereo {
+    // The left and the right eye
+    pub fn eyes(&self, camera: &Camera) -> [Camera; 2] {
+        let offset = camera.right() * (self.eye_distance / 2.0);
+        let toe_in = (self.eye_distance / 2.0)
+            .atan2(self.convergence)
+            .to_degrees();
+        [
+            Camera {
+                pos: camera.pos - offset,
+                yaw: camera.yaw + toe_in,
+                ..*camera
+            },
+            Camera {
+                pos: camera.pos + offset,
+                yaw: camera.yaw - toe_in,
+                ..*camera
+            },
+        ]
+    }
+
+    // Frames of both eyes, as wide as a half of the combined one when they
+    // are shown next to each other
+    pub fn eye_size(&self, width: usize) -> usize {
+        match self.mode {
+            StereoMode::SideBySide | StereoMode::CrossEyed => width / 2,
+            _ => width,
+        }
+    }
+
+    // Puts the frames of both eyes together into one
+    pub fn combine(&self, left: &Framebuffer, right: &Framebuffer, into: &mut Framebuffer) {
+        match self.mode {
+            StereoMode::Off => (),
+            StereoMode::Anaglyph => anaglyph(left, right, into),
+            StereoMode::SideBySide => {
+                copy(left, into, 0);
+                copy(right, into, into.width - right.width);
+            }
+            StereoMode::CrossEyed => {
+                copy(right, into, 0);
+                copy(left, into, into.width - left.width);
+            }
+        }
+    }
+}
+
+// The brightness of the left eye goes into the red channel and that of the
+// right eye into green and blue, wherever either eye drew something
+fn anaglyph(left: &Framebuffer, right: &Framebuffer, into: &mut Framebuffer) {
+    let brightness = |framebuffer: &Framebuffer, i: usize| {
+        let value = color::brightness(framebuffer.color[i]);
+        (value as f32 * framebuffer.coverage[i]).round() as u8
+    };
+    for i in 0..into.color.len() {
+        let coverage = left.coverage[i].max(right.coverage[i]);
+        if coverage <= 0.0 {
+            continue;
+        }
+        let (red, cyan) = (brightness(left, i), brightness(right, i));
+        into.color[i] = [red, cyan, cyan];
+        into.coverage[i] = coverage;
+        into.depth[i] = left.depth[i].min(right.depth[i]);
+    }
+}
+
+fn copy(from: &Framebuffer, into: &mut Framebuffer, x: usize) {
+    for y in 0..from.height.min(into.height) {
+        let source = from.index(0, y);
+        let target = into.index(x, y);
+        into.color[target..target + from.width]
+            .copy_from_slice(&from.color[source..source + from.width]);
+        into.depth[target..target + from.width]
+            .copy_from_slice(&from.depth[source..source + from.width]);
+        into.coverage[target..target + from.width]
+            .copy_from_slice(&from.coverage[source..source + from.width]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Vec3;
+
+    #[test]
+    fn eyes_converge_on_the_same_point() {
+        let camera = Camera {
+            pos: Vec3::new(1.0, 0.5, -2.0),
+            yaw: 30.0,
+            ..Default::default()
+        };
+        let stereo = Stereo {
+            convergence: 3.0,
+            ..Default::default()
+        };
+        let target = camera.pos + camera.forward() * stereo.convergence;
+        let [left, right] = stereo.eyes(&camera);
+
+        assert!(((right.pos - left.pos).length() - stereo.eye_distance).abs() < 1e-9);
+        for eye in [left, right] {
+            let (x, y) = eye.project_vertex(&target);
+            assert!(x.abs() < 1e-9 && y.abs() < 1e-9, "({}, {})", x, y);
+        }
+        // closer points are further right for the left eye
+        let near = camera.pos + camera.forward() * 1.0;
+        assert!(left.project_vertex(&near).0 > right.project_vertex(&near).0);
+    }
+
+    #[test]
+    fn anaglyph_splits_eyes_into_channels() {
+        let mut left = Framebuffer::new(2, 1, [0, 0, 0]);
+        let mut right = Framebuffer::new(2, 1, [0, 0, 0]);
+        left.plot(0, 0, 1.0, [255, 0, 0], 1.0);
+        right.plot(1, 0, 1.0, [0, 0, 200], 1.0);
+
+        let stereo = Stereo {
+            mode: StereoMode::Anaglyph,
+            ..Default::default()
+        };
+        let mut combined = Framebuffer::new(2, 1, [0, 0, 0]);
+        stereo.combine(&left, &right, &mut combined);
+        assert_eq!(combined.color, vec![[255, 0, 0], [0, 200, 200]]);
+        assert_eq!(combined.coverage, vec![1.0, 1.0]);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::headless::Image;
+use color_eyre::eyre::{eyre, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+// What happens to texture coordinates outside of 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub path: Option<PathBuf>, // of the image it was loaded from
+    // The image followed by its mipmaps, each half the size of the previous
+    levels: Vec<Image>,
+}
+impl Texture {
+    pub fn new(image: Image) -> Result<Self> {
+        if image.width == 0 || image.height == 0 {
+            return Err(eyre!("texture is empty"));
+        }
+        Ok(Self {
+            filter: Filter::Bilinear,
+            wrap: Wrap::Repeat,
+            path: None,
+            levels: vec![image],
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut texture = Self::new(Image::load(path)?)?;
+        texture.path = Some(std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+        Ok(texture)
+    }
+
+    // Averages every 2x2 block down to a single pixel, until there is one
+    // pixel left
+    pub fn with_mipmaps(mut self) -> Self {
+        self.levels.truncate(1);
+        while let Some(last) = self.levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            let (width, height) = (last.width.div_ceil(2), last.height.div_ceil(2));
+            let mut pixels = Vec::with_capacity((width * height) as usize);
+            for y in 0..height {
+                for x in 0..width {
+                    let mut sum = [0u32; 3];
+                    let mut count = 0;
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (sx, sy) = (2 * x + dx, 2 * y + dy);
+                        if sx < last.width && sy < last.height {
+                            let pixel = last.pixels[(sy * last.width + sx) as usize];
+                            (0..3).for_each(|c| sum[c] += pixel[c] as u32);
+                            count += 1;
+                        }
+                    }
+                    pixels.push(sum.map(|c| (c / count) as u8));
+                }
+            }
+            self.levels.push(Image {
+                width,
+                height,
+                pixels,
+            });
+        }
+        self
+    }
+
+    pub fn has_mipmaps(&self) -> bool {
+        self.levels.len() > 1
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.levels[0]
+    }
+
+    // `v` goes up, as in OBJ files. `footprint` is how far apart in texture
+    // coordinates neighbouring samples are, it picks the mipmap level.
+    pub fn sample(&self, u: f64, v: f64, footprint: f64) -> [u8; 3] {
+        let base = &self.levels[0];
+        let texels = footprint * base.width.max(base.height) as f64;
+        let level = if texels > 1.0 {
+            (texels.log2().round() as usize).min(self.levels.len() - 1)
+        } else {
+            0
+        };
+        let image = &self.levels[level];
+
+        let x = u * image.width as f64;
+        let y = (1.0 - v) * image.height as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(image, x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Texel centers are at half coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let corners = [
+                    (self.texel(image, x0, y0), (1.0 - fx) * (1.0 - fy)),
+                    (self.texel(image, x0 + 1, y0), fx * (1.0 - fy)),
+                    (self.texel(image, x0, y0 + 1), (1.0 - fx) * fy),
+                    (self.texel(image, x0 + 1, y0 + 1), fx * fy),
+                ];
+                [0, 1, 2].map(|c| {
+                    let value: f64 = corners
+                        .iter()
+                        .map(|(pixel, weight)| pixel[c] as f64 * weight)
+                        .sum();
+                    value.round().clamp(0.0, 255.0) as u8
+                })
+            }
+        }
+    }
+
+    fn texel(&self, image: &Image, x: i64, y: i64) -> [u8; 3] {
+        let (width, height) = (image.width as i64, image.height as i64);
+        let (x, y) = match self.wrap {
+            Wrap::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+            Wrap::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+        };
+        image.pixels[(y * width + x) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Black and white, with the white texel in the top left
+    fn checker() -> Texture {
+        let image = Image::parse_pnm(b"P2\n# checker\n2 2\n1\n1 0\n0 1\n").unwrap();
+        Texture::new(image).unwrap()
+    }
+
+    #[test]
+    fn reads_plain_and_binary_images() {
+        let plain = Image::parse_pnm(b"P3 2 1 255 255 0 0 0 0 255").unwrap();
+        let binary = Image::parse_pnm(b"P6\n2 1\n255\n\xff\x00\x00\x00\x00\xff").unwrap();
+        assert_eq!(plain.pixels, vec![[255, 0, 0], [0, 0, 255]]);
+        assert_eq!(binary.pixels, plain.pixels);
+        assert_eq!(checker().image().pixels[0], [255, 255, 255]);
+        assert!(Image::parse_pnm(b"P6\n2 1\n255\n\xff").is_err());
+
+        // The sizes overflow when multiplied out
+        let huge = format!("P6\n{0} {0}\n255\n", u32::MAX);
+        let error = Image::parse_pnm(huge.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "image is too large");
+        let wide = format!("P5\n{} 1\n255\n\0", u64::from(u32::MAX) + 1);
+        assert!(Image::parse_pnm(wide.as_bytes()).is_err());
+
+        let mut png = Vec::new();
+        plain.write_png(&mut png).unwrap();
+        assert_eq!(Image::parse_png(&png).unwrap().pixels, plain.pixels);
+    }
+
+    #[test]
+    fn samples_with_filters_and_wrapping() {
+        let mut texture = checker();
+        texture.filter = Filter::Nearest;
+        // v goes up, so the top left texel is at v close to 1
+        assert_eq!(texture.sample(0.25, 0.75, 0.0), [255; 3]);
+        assert_eq!(texture.sample(0.75, 0.75, 0.0), [0; 3]);
+        assert_eq!(texture.sample(1.25, 0.75, 0.0), [255; 3]);
+        texture.wrap = Wrap::Clamp;
+        assert_eq!(texture.sample(1.25, 0.75, 0.0), [0; 3]);
+
+        texture.filter = Filter::Bilinear;
+        assert_eq!(texture.sample(0.5, 0.5, 0.0), [128; 3]);
+        assert_eq!(texture.sample(0.25, 0.75, 0.0), [255; 3]);
+    }
+
+    #[test]
+    fn mipmaps_average_distant_samples() {
+        let mut texture = checker().with_mipmaps();
+        texture.filter = Filter::Nearest;
+        assert!(texture.has_mipmaps());
+        assert_eq!(texture.sample(0.25, 0.75, 0.01), [255; 3]);
+        assert_eq!(texture.sample(0.25, 0.75, 1.0), [127; 3]);
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 
 pub struct Vec3 {
     pub x: f64,
@@ -24,11 +24,15 @@ impl Vec3 {
         }
     }
 
+    pub fn dot(&self, other: Vec3) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
     pub fn cross(&self, other: Vec3) -> Vec3 {
         Vec3 {
             x: self.y * other.z - self.z * other.y,
             y: self.z * other.x - self.x * other.z,
-            z: self.x * other.y - self.y * other.z,
+            z: self.x * other.y - self.y * other.x,
         }
     }
 }
@@ -107,12 +111,15 @@ impl std::ops::Mul<f64> for Vec2 {
 pub struct MyShapes {
     pub _cube_vertices: [Vec3; 8],
     pub _cube_edges: [(usize, usize); 12],
+    pub _cube_faces: [[usize; 3]; 12],
     // pub _cube_bounds: Vec<(Vec3, Vec3)>,
     pub _pyramid_vertices: [Vec3; 5],
     pub _pyramid_edges: [(usize, usize); 8],
+    pub _pyramid_faces: [[usize; 3]; 6],
 
     pub _prism_vertices: [Vec3; 6],
     pub _prism_edges: [(usize, usize); 9],
+    pub _prism_faces: [[usize; 3]; 8],
 
     pub _tesseract_vertices: [Vec3; 16],
     pub _tesseract_edges: [(usize, usize); 32],
@@ -145,6 +152,22 @@ impl MyShapes {
             (3, 7),
         ];
 
+        // Faces are wound so that (b - a) x (c - a) points out of the shape
+        let _cube_faces = [
+            [4, 7, 6],
+            [4, 6, 5],
+            [0, 1, 2],
+            [0, 2, 3],
+            [1, 5, 6],
+            [1, 6, 2],
+            [0, 3, 7],
+            [0, 7, 4],
+            [3, 2, 6],
+            [3, 6, 7],
+            [0, 4, 5],
+            [0, 5, 1],
+        ];
+
         let _pyramid_vertices = [
             Vec3::new(-0.5, -0.5, 3.0), // base 0
             Vec3::new(0.5, -0.5, 3.0),  // base 1
@@ -164,6 +187,15 @@ impl MyShapes {
             (3, 4),
         ];
 
+        let _pyramid_faces = [
+            [0, 1, 2],
+            [0, 2, 3],
+            [0, 4, 1],
+            [1, 4, 2],
+            [2, 4, 3],
+            [3, 4, 0],
+        ];
+
         let _prism_vertices = [
             Vec3::new(-0.5, -0.5, 3.5), // base triangle
             Vec3::new(0.5, -0.5, 3.5),
@@ -185,6 +217,17 @@ impl MyShapes {
             (2, 5),
         ];
 
+        let _prism_faces = [
+            [0, 1, 2],
+            [3, 5, 4],
+            [0, 3, 4],
+            [0, 4, 1],
+            [1, 4, 5],
+            [1, 5, 2],
+            [2, 5, 3],
+            [2, 3, 0],
+        ];
+
         let _tesseract_vertices = [
             // Front cube
             Vec3::new(-0.5, -0.5, 3.5),
@@ -247,12 +290,94 @@ impl MyShapes {
         Self {
             _cube_vertices,
             _cube_edges,
+            _cube_faces,
             _pyramid_vertices,
             _pyramid_edges,
+            _pyramid_faces,
             _prism_vertices,
             _prism_edges,
+            _prism_faces,
             _tesseract_vertices,
             _tesseract_edges,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn vec3() -> impl Strategy<Value = Vec3> {
+        (-100.0..100.0, -100.0..100.0, -100.0..100.0).prop_map(|(x, y, z)| Vec3::new(x, y, z))
+    }
+
+    #[test]
+    fn cross_of_unit_axes() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+
+        let xy = x.cross(y);
+        assert_eq!((xy.x, xy.y, xy.z), (0.0, 0.0, 1.0));
+        let yz = y.cross(z);
+        assert_eq!((yz.x, yz.y, yz.z), (1.0, 0.0, 0.0));
+        let zx = z.cross(x);
+        assert_eq!((zx.x, zx.y, zx.z), (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn cross_of_general_vectors() {
+        let c = Vec3::new(1.0, 2.0, 3.0).cross(Vec3::new(4.0, 5.0, 6.0));
+        assert_eq!((c.x, c.y, c.z), (-3.0, 6.0, -3.0));
+    }
+
+    #[test]
+    fn normalize_zero_vector() {
+        let n = Vec3::new(0.0, 0.0, 0.0).normalize();
+        assert_eq!((n.x, n.y, n.z), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(0.5, -1.0, 2.0);
+
+        let sum = a + b;
+        assert_eq!((sum.x, sum.y, sum.z), (1.5, 1.0, 5.0));
+        let diff = a - b;
+        assert_eq!((diff.x, diff.y, diff.z), (0.5, 3.0, 1.0));
+        let scaled = a * 2.0;
+        assert_eq!((scaled.x, scaled.y, scaled.z), (2.0, 4.0, 6.0));
+        assert_eq!(Vec3::new(3.0, 4.0, 0.0).length(), 5.0);
+    }
+
+    proptest! {
+        #[test]
+        fn cross_is_orthogonal(a in vec3(), b in vec3()) {
+            let c = a.cross(b);
+            let tolerance = 1e-9 * a.length() * b.length() * (a.length() + b.length() + 1.0);
+            prop_assert!(c.dot(a).abs() <= tolerance);
+            prop_assert!(c.dot(b).abs() <= tolerance);
+        }
+
+        #[test]
+        fn cross_is_anticommutative(a in vec3(), b in vec3()) {
+            let ab = a.cross(b);
+            let ba = b.cross(a);
+            prop_assert_eq!((ab.x, ab.y, ab.z), (-ba.x, -ba.y, -ba.z));
+        }
+
+        #[test]
+        fn normalize_has_unit_length(a in vec3()) {
+            prop_assume!(a.length() > 1e-6);
+            prop_assert!((a.normalize().length() - 1.0).abs() < 1e-9);
+        }
+
+        #[test]
+        fn sub_undoes_add(a in vec3(), b in vec3()) {
+            let c = (a + b) - b;
+            prop_assert!((c - a).length() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::app::App;
+use termulator::camera::Projection;
+use termulator::render::SELECTED_COLOR;
+use termulator::stereo::StereoMode;
+use termulator::viewport::RenderMode;
+
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListState, Paragraph},
+    Frame,
+};
+
+// Columns of the inspector next to the viewport
+pub const INSPECTOR_WIDTH: u16 = 30;
+
+// The single viewport, or the four of the quad view with the active one
+// highlighted
+pub fn render_viewports(frame: &mut Frame, app: &mut App, area: Rect) {
+    // The states are taken out while rendering, since the widgets borrow the
+    // rest of the app
+    let Some(mut quad) = app.quad.take() else {
+        let mut state = std::mem::take(&mut app.viewport);
+        frame.render_stateful_widget(app.viewport_widget(app.draw_mode), area, &mut state);
+        app.viewport = state;
+        return;
+    };
+
+    let [top, bottom] =
+        Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(area);
+    let columns = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
+    let [top_left, top_right] = columns.areas(top);
+    let [bottom_left, bottom_right] = columns.areas(bottom);
+    let areas = [top_left, top_right, bottom_left, bottom_right];
+
+    for (index, pane) in quad.panes.iter_mut().enumerate() {
+        let active = index == quad.active;
+        let block = Block::bordered().title(pane.title).border_style(if active {
+            Style::default().fg(SELECTED_COLOR)
+        } else {
+            Style::default()
+        });
+        let inner = block.inner(areas[index]);
+        frame.render_widget(block, areas[index]);
+
+        if active {
+            let mut state = std::mem::take(&mut app.viewport);
+            frame.render_stateful_widget(app.viewport_widget(app.draw_mode), inner, &mut state);
+            app.viewport = state;
+        } else {
+            frame.render_stateful_widget(
+                app.viewport_widget(pane.draw_mode),
+                inner,
+                &mut pane.state,
+            );
+        }
+    }
+    app.quad = Some(quad);
+}
+
+// Frame and camera info in the bottom left corner of the viewport. The top
+// left cell is kept free since graphics output writes its image there.
+pub fn render_hud(frame: &mut Frame, app: &App, area: Rect) {
+    let camera = &app.viewport.camera;
+    let stats = &app.viewport.stats;
+    let mut lines = vec![
+        Line::raw(format!(
+            "{:.1} fps, render {:.1} ms",
+            app.fps,
+            stats.render_time.as_secs_f64() * 1000.0
+        )),
+        Line::raw(format!(
+            "pos {:.2} {:.2} {:.2}",
+            camera.pos.x, camera.pos.y, camera.pos.z
+        )),
+        Line::raw(match camera.projection {
+            Projection::Perspective => format!(
+                "yaw {:.1} pitch {:.1} fov {:.0}",
+                camera.yaw, camera.pitch, camera.fov
+            ),
+            Projection::Orthographic | Projection::Oblique(_) => format!(
+                "yaw {:.1} pitch {:.1} extent {:.2}",
+                camera.yaw, camera.pitch, camera.extent
+            ),
+            Projection::Fisheye(fov) => format!(
+                "yaw {:.1} pitch {:.1} fov {:.0}",
+                camera.yaw, camera.pitch, fov
+            ),
+            Projection::Equirectangular => {
+                format!("yaw {:.1} pitch {:.1}", camera.yaw, camera.pitch)
+            }
+        }),
+        Line::raw(match camera.projection {
+            Projection::Perspective => "Perspective projection",
+            Projection::Orthographic => "Orthographic projection",
+            Projection::Oblique(1.0) => "Oblique projection, cavalier",
+            Projection::Oblique(_) => "Oblique projection, cabinet",
+            Projection::Fisheye(_) => "Fisheye projection",
+            Projection::Equirectangular => "Equirectangular projection",
+        }),
+        Line::raw(match app.render_mode {
+            RenderMode::Vertex => {
+                format!("{:?} Vertex, lines {:?}", app.draw_mode, app.hidden_lines)
+            }
+            RenderMode::Solid | RenderMode::Raycast | RenderMode::Raymarch => format!(
+                "{:?} {:?}, shadows {:?}",
+                app.draw_mode, app.render_mode, app.shadows
+            ),
+            RenderMode::PathTrace => format!(
+                "{:?} PathTrace, {} samples",
+                app.draw_mode, app.viewport.stats.samples
+            ),
+        }),
+        Line::raw(format!(
+            "terminal {}x{}, {}x{} px",
+            app.terminal_size.x, app.terminal_size.y, stats.width, stats.height
+        )),
+        Line::raw(format!(
+            "{}/{} edges, {} faces",
+            stats.segments, stats.edges, stats.faces
+        )),
+        Line::raw(format!("{} pixels", stats.pixels)),
+    ];
+    if stats.culled > 0 || stats.hidden > 0 {
+        lines.push(Line::raw(format!(
+            "{} faces culled, {} hidden",
+            stats.culled, stats.hidden
+        )));
+    }
+    if app.stereo.mode != StereoMode::Off {
+        lines.push(Line::raw(format!(
+            "{:?}, eyes {:.3} apart, converging at {:.2}",
+            app.stereo.mode, app.stereo.eye_distance, app.stereo.convergence
+        )));
+    }
+    if let Some(object) = app
+        .selected_object
+        .and_then(|index| app.scene.objects.get(index))
+    {
+        let position = object.transform.position;
+        lines.push(Line::styled(
+            format!("selected {} ({})", object.name, object.mesh.name),
+            Style::default().fg(SELECTED_COLOR),
+        ));
+        lines.push(Line::raw(format!(
+            "at {:.2} {:.2} {:.2}",
+            position.x, position.y, position.z
+        )));
+        lines.push(Line::raw(format!(
+            "{} vertices, {} faces",
+            object.mesh.vertices.len(),
+            object.mesh.faces.len()
+        )));
+    }
+    if let Some(gizmo) = &app.gizmo {
+        let snap = if gizmo.snap {
+            format!("snap {}", gizmo.step())
+        } else {
+            String::from("no snap")
+        };
+        lines.push(Line::raw(format!(
+            "gizmo {:?} {:?}, {}",
+            gizmo.mode, gizmo.axis, snap
+        )));
+    }
+    if let Some(edit) = &app.edit {
+        lines.push(Line::raw(format!(
+            "edit {} selected, axis {:?}",
+            edit.editor.selection.len(),
+            edit.axis
+        )));
+    }
+
+    let width = lines.iter().map(|line| line.width()).max().unwrap_or(0) as u16 + 2;
+    let height = lines.len() as u16 + 2;
+    if area.width < width || area.height < height + 1 {
+        return;
+    }
+    let hud_area = Rect::new(area.x, area.bottom() - height, width, height);
+
+    frame.render_widget(Clear, hud_area);
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered()), hud_area);
+}
+
+// Objects and shapes of the scene with the details of the selected object
+pub fn render_inspector(frame: &mut Frame, app: &App, area: Rect) {
+    let [list_area, details_area] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(11)]).areas(area);
+
+    let items = app
+        .scene
+        .objects
+        .iter()
+        .map(|object| format!("{} ({})", object.name, object.mesh.name))
+        // Shapes come after the objects, so they don't shift their indices
+        .chain(
+            app.scene
+                .shapes
+                .iter()
+                .map(|shape| format!("{} (sdf)", shape.name)),
+        );
+    let list = List::new(items)
+        .block(Block::bordered().title("scene"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    let mut list_state = ListState::default().with_selected(app.selected_object);
+    frame.render_stateful_widget(list, list_area, &mut list_state);
+
+    let selected = app
+        .selected_object
+        .and_then(|index| app.scene.objects.get(index));
+    let details = match selected {
+        Some(object) => {
+            let transform = &object.transform;
+            let vec = |v: termulator::Vec3| format!("{:.2} {:.2} {:.2}", v.x, v.y, v.z);
+            let material = &object.material;
+            let texture = material
+                .texture
+                .as_ref()
+                .map_or(String::from("none"), |texture| {
+                    let image = texture.image();
+                    let name = texture
+                        .path
+                        .as_ref()
+                        .and_then(|path| path.file_name())
+                        .map_or(String::new(), |name| format!("{} ", name.to_string_lossy()));
+                    format!("{}{}x{}", name, image.width, image.height)
+                });
+            vec![
+                Line::raw(format!("mesh: {}", object.mesh.name)),
+                Line::raw(format!("position: {}", vec(transform.position))),
+                Line::raw(format!("rotation: {}", vec(transform.rotation))),
+                Line::raw(format!("scale: {}", vec(transform.scale))),
+                Line::raw(format!("vertices: {}", object.mesh.vertices.len())),
+                Line::raw(format!("edges: {}", object.mesh.edges.len())),
+                Line::raw(format!("faces: {}", object.mesh.faces.len())),
+                Line::from(vec![
+                    Span::raw(format!("material: {} ", material.name)),
+                    Span::styled("██", Style::default().fg(material.color)),
+                ]),
+                Line::raw(format!("texture: {}", texture)),
+            ]
+        }
+        None => vec![Line::raw("nothing selected")],
+    };
+    let title = selected.map_or("object", |object| object.name.as_str());
+    frame.render_widget(
+        Paragraph::new(details).block(Block::bordered().title(title)),
+        details_area,
+    );
+}
@@ -0,0 +1,463 @@
+use crate::camera::Camera;
+use crate::color::{self, ColorSupport, Dither};
+use crate::framebuffer::Framebuffer;
+use crate::gizmo::Gizmo;
+use crate::headless::Image;
+use crate::metrics;
+use crate::output::{DrawMode, OutputBackend};
+use crate::render::{self, Accumulation, HiddenLines, ShadowMaps, Shadows};
+use crate::scene::{Object, Scene};
+use crate::stereo::{Stereo, StereoMode};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Position, Rect},
+    style::Color,
+    widgets::StatefulWidget,
+};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    Vertex,
+    Solid,
+    Raycast,
+    Raymarch,
+    PathTrace,
+}
+
+// Everything that has to persist between frames of a viewport
+#[derive(Debug, Clone, Default)]
+pub struct ViewportState {
+    pub camera: Camera,
+    pub stats: RenderStats, // of the last rendered frame
+    pub area: Rect,         // the last frame was rendered into
+    // Of the lights in the solid mode, shared by both eyes in stereo
+    pub shadow_maps: ShadowMaps,
+    // Samples of the path trace mode, of each eye in stereo
+    pub accumulation: [Accumulation; 2],
+}
+impl ViewportState {
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            stats: RenderStats::default(),
+            area: Rect::default(),
+            shadow_maps: ShadowMaps::default(),
+            accumulation: Default::default(),
+        }
+    }
+
+    // The object drawn in a terminal cell of the last frame
+    pub fn pick(&self, scene: &Scene, column: u16, row: u16) -> Option<usize> {
+        if !self.area.contains(Position::new(column, row)) {
+            return None;
+        }
+        let (u, v) = self.to_ndc(column, row);
+        render::pick(scene, &self.camera, u, v).map(|hit| hit.object)
+    }
+
+    // The vertex of an object closest to a terminal cell of the last frame,
+    // when it is drawn within a cell of it
+    pub fn pick_vertex(&self, object: &Object, column: u16, row: u16) -> Option<usize> {
+        if !self.area.contains(Position::new(column, row)) {
+            return None;
+        }
+        let (u, v) = self.to_ndc(column, row);
+        let (cell_width, cell_height) =
+            (2.0 / self.area.width as f64, 2.0 / self.area.height as f64);
+        object
+            .world_vertices()
+            .iter()
+            .enumerate()
+            .filter(|(_, vertex)| {
+                let view = self.camera.apply_view_transform(**vertex - self.camera.pos);
+                self.camera.depth(view) > self.camera.near_plane
+            })
+            .map(|(index, vertex)| {
+                let (x, y) = self.camera.project_vertex(vertex);
+                let (dx, dy) = ((x - u) / cell_width, (y - v) / cell_height);
+                (index, dx * dx + dy * dy)
+            })
+            .filter(|&(_, distance)| distance <= 1.0)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+
+    // The center of a terminal cell in normalized device coordinates of the
+    // last frame, beyond [-1, 1] outside of its area
+    pub fn to_ndc(&self, column: u16, row: u16) -> (f64, f64) {
+        let u = (column as f64 - self.area.x as f64 + 0.5) / self.area.width as f64;
+        let v = (row as f64 - self.area.y as f64 + 0.5) / self.area.height as f64;
+        (u * 2.0 - 1.0, 1.0 - v * 2.0)
+    }
+}
+
+// What went into a frame and how long it took
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub width: usize, // of the framebuffer
+    pub height: usize,
+    pub objects: usize,
+    pub vertices: usize,
+    pub edges: usize,
+    pub faces: usize,
+    pub segments: usize, // edges left after clipping
+    pub pixels: usize,   // covered by the scene
+    pub culled: usize,   // faces turned away in hidden line wireframes
+    pub hidden: usize,   // pieces of edges behind faces
+    pub samples: usize,  // per pixel, accumulated by the path trace mode
+    pub render_time: Duration,
+}
+
+// Renders a scene into any area as seen from the camera in its `ViewportState`
+pub struct Viewport3D<'a> {
+    scene: &'a Scene,
+    draw_mode: DrawMode,
+    backend: Option<&'a dyn OutputBackend>,
+    render_mode: RenderMode,
+    hidden_lines: HiddenLines,
+    shadows: Shadows,
+    samples: usize,
+    background: Color,
+    color_support: ColorSupport,
+    dither: Dither,
+    cell_aspect: f64,
+    selected: Option<usize>,
+    gizmo: Option<Gizmo>,
+    edit: Option<&'a [usize]>,
+    stereo: Stereo,
+}
+impl<'a> Viewport3D<'a> {
+    pub fn new(scene: &'a Scene) -> Self {
+        Self {
+            scene,
+            draw_mode: DrawMode::Braille,
+            backend: None,
+            render_mode: RenderMode::Vertex,
+            hidden_lines: HiddenLines::Show,
+            shadows: Shadows::Hard,
+            samples: 1,
+            background: Color::Reset,
+            color_support: ColorSupport::TrueColor,
+            dither: Dither::None,
+            cell_aspect: metrics::DEFAULT_CELL_ASPECT,
+            selected: None,
+            gizmo: None,
+            edit: None,
+            stereo: Stereo::default(),
+        }
+    }
+
+    pub fn draw_mode(mut self, draw_mode: DrawMode) -> Self {
+        self.draw_mode = draw_mode;
+        self
+    }
+
+    // Overrides the backend of the draw mode with a custom one
+    pub fn backend(mut self, backend: &'a dyn OutputBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    // How wireframes draw the edges behind faces
+    pub fn hidden_lines(mut self, hidden_lines: HiddenLines) -> Self {
+        self.hidden_lines = hidden_lines;
+        self
+    }
+
+    // Whether the lights of the scene cast shadows in the raycast and raymarch
+    // modes
+    pub fn shadows(mut self, shadows: Shadows) -> Self {
+        self.shadows = shadows;
+        self
+    }
+
+    // Paths traced through every pixel per frame in the path trace mode
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    // Limits the output to the colors the terminal can show
+    pub fn color_support(mut self, color_support: ColorSupport) -> Self {
+        self.color_support = color_support;
+        self
+    }
+
+    // How colors between the ones the terminal can show are approximated
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    // Height over width of a terminal cell, see `metrics::cell_aspect`
+    pub fn cell_aspect(mut self, cell_aspect: f64) -> Self {
+        self.cell_aspect = cell_aspect;
+        self
+    }
+
+    // Highlights the edges of an object, by its index in the scene
+    pub fn selected(mut self, selected: Option<usize>) -> Self {
+        self.selected = selected;
+        self
+    }
+
+    // Draws the handles of a gizmo at the selected object
+    pub fn gizmo(mut self, gizmo: Option<Gizmo>) -> Self {
+        self.gizmo = gizmo;
+        self
+    }
+
+    // Marks the vertices of the selected object for editing, the ones at
+    // these indices as selected
+    pub fn edit(mut self, selection: Option<&'a [usize]>) -> Self {
+        self.edit = selection;
+        self
+    }
+
+    // Renders a picture for each eye, unless its mode is off
+    pub fn stereo(mut self, stereo: Stereo) -> Self {
+        self.stereo = stereo;
+        self
+    }
+
+    // Renders at an arbitrary pixel resolution instead of into terminal cells
+    pub fn render_image(&self, state: &mut ViewportState, width: u32, height: u32) -> Image {
+        // Pixels are square, unlike terminal cells
+        state.camera.aspect_ratio = height as f64 / width as f64;
+
+        let background = color::to_rgb(self.background, [0, 0, 0]);
+        let mut framebuffer = Framebuffer::new(width as usize, height as usize, background);
+        state.stats = self.draw(
+            &state.camera,
+            &mut state.shadow_maps,
+            &mut state.accumulation,
+            &mut framebuffer,
+        );
+        Image::from_framebuffer(&framebuffer)
+    }
+
+    fn draw(
+        &self,
+        camera: &Camera,
+        shadow_maps: &mut ShadowMaps,
+        accumulation: &mut [Accumulation; 2],
+        framebuffer: &mut Framebuffer,
+    ) -> RenderStats {
+        let start = Instant::now();
+        let mut stats = RenderStats {
+            width: framebuffer.width,
+            height: framebuffer.height,
+            objects: self.scene.objects.len(),
+            ..Default::default()
+        };
+        for object in &self.scene.objects {
+            stats.vertices += object.mesh.vertices.len();
+            stats.edges += object.mesh.edges.len();
+            stats.faces += object.mesh.faces.len();
+        }
+
+        if self.stereo.mode == StereoMode::Off {
+            self.draw_scene(
+                camera,
+                shadow_maps,
+                &mut accumulation[0],
+                framebuffer,
+                &mut stats,
+            );
+        } else {
+            let width = self.stereo.eye_size(framebuffer.width);
+            let [left_eye, right_eye] = self.stereo.eyes(camera);
+            let [left_samples, right_samples] = accumulation;
+            let mut draw_eye = |mut eye: Camera, accumulation| {
+                // Narrower frames make for a taller aspect ratio
+                eye.aspect_ratio *= framebuffer.width as f64 / width as f64;
+                let mut frame = Framebuffer::new(width, framebuffer.height, framebuffer.background);
+                self.draw_scene(&eye, shadow_maps, accumulation, &mut frame, &mut stats);
+                frame
+            };
+            let (left, right) = (
+                draw_eye(left_eye, left_samples),
+                draw_eye(right_eye, right_samples),
+            );
+            self.stereo.combine(&left, &right, framebuffer);
+        }
+        color::quantize(framebuffer, self.color_support, self.dither);
+
+        stats.pixels = framebuffer.coverage.iter().filter(|&&c| c > 0.0).count();
+        stats.render_time = start.elapsed();
+        stats
+    }
+
+    fn draw_scene(
+        &self,
+        camera: &Camera,
+        shadow_maps: &mut ShadowMaps,
+        accumulation: &mut Accumulation,
+        framebuffer: &mut Framebuffer,
+        stats: &mut RenderStats,
+    ) {
+        let selected = self.selected.and_then(|i| self.scene.objects.get(i));
+        match self.render_mode {
+            RenderMode::Vertex => {
+                let segments = match self.hidden_lines {
+                    HiddenLines::Show => render::project_edges(self.scene, camera, self.selected),
+                    hidden_lines => {
+                        let wireframe = render::project_hidden_edges(
+                            self.scene,
+                            camera,
+                            self.selected,
+                            hidden_lines,
+                        );
+                        stats.culled = wireframe.culled;
+                        stats.hidden = wireframe.hidden;
+                        wireframe.segments
+                    }
+                };
+                stats.segments = segments.len();
+                for segment in segments {
+                    framebuffer.draw_segment(&segment);
+                }
+            }
+            RenderMode::Solid
+            | RenderMode::Raycast
+            | RenderMode::Raymarch
+            | RenderMode::PathTrace => {
+                match self.render_mode {
+                    RenderMode::Solid => render::rasterize(
+                        self.scene,
+                        camera,
+                        self.shadows,
+                        shadow_maps,
+                        framebuffer,
+                    ),
+                    RenderMode::Raycast => {
+                        render::raycast(self.scene, camera, self.shadows, framebuffer)
+                    }
+                    RenderMode::Raymarch => {
+                        render::raymarch(self.scene, camera, self.shadows, framebuffer)
+                    }
+                    _ => {
+                        render::path_trace(
+                            self.scene,
+                            camera,
+                            self.samples,
+                            accumulation,
+                            framebuffer,
+                        );
+                        stats.samples = accumulation.samples;
+                    }
+                }
+
+                // Outline the selected object, pulled slightly towards the
+                // camera so its faces don't hide the edges they share
+                if let Some(object) = selected {
+                    for mut segment in
+                        render::project_object_edges(object, camera, render::SELECTED_COLOR)
+                    {
+                        segment.z1 *= 0.99;
+                        segment.z2 *= 0.99;
+                        framebuffer.draw_segment(&segment);
+                    }
+                }
+            }
+        }
+
+        if let (Some(gizmo), Some(object)) = (self.gizmo, selected) {
+            for segment in gizmo.segments(object, camera) {
+                framebuffer.draw_segment(&segment);
+            }
+        }
+        if let (Some(selection), Some(object)) = (self.edit, selected) {
+            draw_vertices(framebuffer, camera, object, selection);
+        }
+    }
+}
+
+impl StatefulWidget for Viewport3D<'_> {
+    type State = ViewportState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        if area.is_empty() {
+            return;
+        }
+        state.camera.aspect_ratio = aspect_ratio(area.width, area.height, self.cell_aspect);
+        state.area = area;
+
+        let draw_mode = match (self.draw_mode, self.color_support) {
+            // Without colors both halves of a cell would look the same
+            (DrawMode::Pixels, ColorSupport::Monochrome) => DrawMode::HalfBlock,
+            (draw_mode, _) => draw_mode,
+        };
+        let backend = self.backend.unwrap_or(draw_mode.backend());
+        let (cell_width, cell_height) = backend.cell_size();
+        let mut framebuffer = Framebuffer::new(
+            area.width as usize * cell_width,
+            area.height as usize * cell_height,
+            color::to_rgb(self.background, [0, 0, 0]),
+        );
+
+        state.stats = self.draw(
+            &state.camera,
+            &mut state.shadow_maps,
+            &mut state.accumulation,
+            &mut framebuffer,
+        );
+
+        backend.render(&framebuffer, area, buf, self.background);
+
+        if self.color_support != ColorSupport::TrueColor {
+            for position in area.positions() {
+                let cell = &mut buf[position];
+                cell.fg = self.color_support.convert(cell.fg);
+                cell.bg = self.color_support.convert(cell.bg);
+            }
+        }
+    }
+}
+
+// A dot on every vertex and a cross on the selected ones, over everything else
+fn draw_vertices(
+    framebuffer: &mut Framebuffer,
+    camera: &Camera,
+    object: &Object,
+    selection: &[usize],
+) {
+    let dot = color::to_rgb(Color::White, [255, 255, 255]);
+    let cross = color::to_rgb(render::SELECTED_COLOR, [255, 255, 0]);
+    for (index, vertex) in object.world_vertices().iter().enumerate() {
+        let view = camera.apply_view_transform(*vertex - camera.pos);
+        if camera.depth(view) <= camera.near_plane {
+            continue;
+        }
+        let (x, y) = camera.project_view_point(view);
+        let (px, py) = framebuffer.to_pixel(x, y);
+        let (px, py) = (px.round() as i64, py.round() as i64);
+
+        if selection.contains(&index) {
+            for d in -2..=2 {
+                framebuffer.plot(px + d, py, camera.near_plane, cross, 1.0);
+                framebuffer.plot(px, py + d, camera.near_plane, cross, 1.0);
+            }
+        } else {
+            framebuffer.plot(px, py, camera.near_plane, dot, 1.0);
+        }
+    }
+}
+
+// Height over width of an area in the same units. The sub-cell pixels of the
+// output backends don't change it, the framebuffer always covers the area.
+pub fn aspect_ratio(cols: u16, rows: u16, cell_aspect: f64) -> f64 {
+    rows as f64 / cols as f64 * cell_aspect
+}